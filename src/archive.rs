@@ -0,0 +1,407 @@
+use crate::{
+    config::{parse_size, ArchiveConfig},
+    error::{NomnomError, Result},
+    walker::{EntrySource, FileEntry, Walker},
+};
+use flate2::read::GzDecoder;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{debug, info, warn};
+
+/// Archive formats nomnom reads in place of a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+/// File name suffixes of each format, matched case-insensitively
+const SUFFIXES: &[(&str, ArchiveKind)] = &[
+    (".tar.gz", ArchiveKind::TarGz),
+    (".tgz", ArchiveKind::TarGz),
+    (".tar", ArchiveKind::Tar),
+    (".zip", ArchiveKind::Zip),
+];
+
+impl ArchiveKind {
+    /// Recognises an archive by its file name
+    pub fn of(path: &Path) -> Option<Self> {
+        split_suffix(path).map(|(_, kind)| kind)
+    }
+
+    /// The file name without its archive suffix, e.g. `release-1.2` for
+    /// `release-1.2.tar.gz`
+    pub fn stem(path: &Path) -> Option<String> {
+        split_suffix(path).map(|(stem, _)| stem)
+    }
+
+    fn is_compressed(self) -> bool {
+        self != ArchiveKind::Tar
+    }
+}
+
+fn split_suffix(path: &Path) -> Option<(String, ArchiveKind)> {
+    let name = path.file_name()?.to_str()?;
+    // ASCII lowercasing keeps byte offsets, so the stem can be cut from `name`
+    let lower = name.to_ascii_lowercase();
+    SUFFIXES
+        .iter()
+        .find(|(suffix, _)| lower.len() > suffix.len() && lower.ends_with(suffix))
+        .map(|(suffix, kind)| (name[..name.len() - suffix.len()].to_string(), *kind))
+}
+
+/// Caps what one archive may unpack to, so a zip bomb fails fast instead of
+/// filling memory
+struct Guard<'a> {
+    archive: &'a Path,
+    /// Unpacked bytes allowed in total
+    limit: u64,
+    /// Unpacked bytes still allowed
+    remaining: u64,
+    members: usize,
+    max_members: usize,
+}
+
+impl<'a> Guard<'a> {
+    fn new(archive: &'a Path, kind: ArchiveKind, config: &ArchiveConfig) -> Result<Self> {
+        let mut limit = parse_size(&config.max_unpacked_size)?;
+        if kind.is_compressed() {
+            let packed = std::fs::metadata(archive)?.len();
+            limit = limit.min(packed.saturating_mul(config.max_ratio));
+        }
+        Ok(Self {
+            archive,
+            limit,
+            remaining: limit,
+            members: 0,
+            max_members: config.max_entries,
+        })
+    }
+
+    /// Counts a member, including directories and links
+    fn count(&mut self) -> Result<()> {
+        self.members += 1;
+        if self.members > self.max_members {
+            return Err(NomnomError::Archive(format!(
+                "{:?} has more than {} members (archives.max_entries)",
+                self.archive, self.max_members
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads a member into memory, stopping as soon as it passes the limit
+    fn read<R: Read>(&mut self, member: R) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        member.take(self.remaining + 1).read_to_end(&mut bytes)?;
+        let len = bytes.len() as u64;
+        if len > self.remaining {
+            return Err(NomnomError::Archive(format!(
+                "{:?} unpacks to more than {} bytes; raise archives.max_unpacked_size \
+                 or archives.max_ratio if it is trusted",
+                self.archive, self.limit
+            )));
+        }
+        self.remaining -= len;
+        Ok(bytes)
+    }
+}
+
+/// Reads the files in a tar, tar.gz or zip archive as walker entries, without
+/// extracting anything to disk
+///
+/// Paths are relative to the archive root, and `absolute_path` is the archive
+/// path joined with them. Include/exclude globs apply relative to the archive
+/// root. Members with absolute paths or `..` components are skipped, as are
+/// directories, links and devices. A later member with the same path replaces
+/// an earlier one, as `tar -x` would.
+pub fn entries(archive: &Path, walker: &Walker) -> Result<Vec<FileEntry>> {
+    let kind = ArchiveKind::of(archive).ok_or_else(|| {
+        NomnomError::Archive(format!(
+            "{:?} is not a .tar, .tar.gz, .tgz or .zip file",
+            archive
+        ))
+    })?;
+    let mut guard = Guard::new(archive, kind, walker.archives())?;
+    debug!(
+        "Reading {:?} as {:?}, up to {} unpacked bytes",
+        archive, kind, guard.limit
+    );
+
+    let mut members = BTreeMap::new();
+    let file = File::open(archive)?;
+    match kind {
+        ArchiveKind::Tar => read_tar(file, &mut guard, &mut members)?,
+        ArchiveKind::TarGz => read_tar(GzDecoder::new(file), &mut guard, &mut members)?,
+        ArchiveKind::Zip => read_zip(file, &mut guard, &mut members)?,
+    }
+    info!("Read {} file(s) from {:?}", members.len(), archive);
+
+    let max_size = walker.max_size()?;
+    let overrides = walker.overrides(archive)?;
+    let mut entries = Vec::new();
+    for (path, bytes) in members {
+        let absolute_path = archive.join(&path);
+        if let Some(ref overrides) = overrides {
+            let excluded = overrides.matched(&absolute_path, false).is_ignore()
+                || absolute_path
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| *dir != archive)
+                    .any(|dir| overrides.matched(dir, true).is_ignore());
+            if excluded {
+                debug!("Excluded in archive: {:?}", path);
+                continue;
+            }
+        }
+
+        let size = bytes.len() as u64;
+        entries.push(FileEntry {
+            is_binary: walker.is_binary_by_extension(&path),
+            is_oversized: size > max_size,
+            path,
+            absolute_path,
+            size,
+            source: EntrySource::Memory(Arc::from(bytes)),
+            change: None,
+            submodule: None,
+            omitted: None,
+        });
+    }
+    Ok(entries)
+}
+
+fn read_tar<R: Read>(
+    reader: R,
+    guard: &mut Guard,
+    members: &mut BTreeMap<PathBuf, Vec<u8>>,
+) -> Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for member in tar.entries()? {
+        let mut member = member?;
+        guard.count()?;
+        if !member.header().entry_type().is_file() {
+            continue;
+        }
+        let name = member.path()?.into_owned();
+        let Some(path) = member_path(&name) else {
+            warn!("Skipping unsafe path {:?} in {:?}", name, guard.archive);
+            continue;
+        };
+        let bytes = guard.read(&mut member)?;
+        members.insert(path, bytes);
+    }
+    Ok(())
+}
+
+fn read_zip(file: File, guard: &mut Guard, members: &mut BTreeMap<PathBuf, Vec<u8>>) -> Result<()> {
+    let zip_error =
+        |e: zip::result::ZipError| NomnomError::Archive(format!("{:?}: {}", guard.archive, e));
+    let mut zip = zip::ZipArchive::new(file).map_err(zip_error)?;
+    for index in 0..zip.len() {
+        guard.count()?;
+        let member = match zip.by_index(index) {
+            Ok(member) => member,
+            Err(e) => {
+                // e.g. encrypted members
+                warn!("Skipping member {} of {:?}: {}", index, guard.archive, e);
+                continue;
+            }
+        };
+        if !member.is_file() {
+            continue;
+        }
+        let name = PathBuf::from(member.name());
+        let Some(path) = member_path(&name) else {
+            warn!("Skipping unsafe path {:?} in {:?}", name, guard.archive);
+            continue;
+        };
+        let bytes = guard.read(member)?;
+        members.insert(path, bytes);
+    }
+    Ok(())
+}
+
+/// The relative path a member unpacks to; `None` for absolute paths and
+/// paths that climb out of the archive with `..`
+fn member_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::{fs, io::Write};
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("proj/src/main.rs", b"fn main() {}\n"),
+        ("proj/logo.png", b"\x89PNG"),
+        ("proj/big.txt", &[b'x'; 2048]),
+    ];
+
+    fn write_tar<W: Write>(writer: W, files: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(writer);
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *content).unwrap();
+        }
+        builder.into_inner().unwrap();
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, content) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn walker(archives: ArchiveConfig) -> Walker {
+        Walker::new(Config {
+            max_size: "1K".to_string(),
+            archives,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn test_archive_kind() {
+        assert_eq!(
+            ArchiveKind::of(Path::new("a/release-1.2.TAR.GZ")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::of(Path::new("bundle.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(ArchiveKind::of(Path::new("x.tar")), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::of(Path::new("x.zip")), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::of(Path::new("x.gz")), None);
+        assert_eq!(ArchiveKind::of(Path::new(".zip")), None);
+        assert_eq!(
+            ArchiveKind::stem(Path::new("release-1.2.tar.gz")).as_deref(),
+            Some("release-1.2")
+        );
+    }
+
+    #[test]
+    fn test_read_each_format() {
+        let dir = TempDir::new().unwrap();
+        let tar_path = dir.path().join("proj.tar");
+        write_tar(File::create(&tar_path).unwrap(), FILES);
+        let tgz_path = dir.path().join("proj.tar.gz");
+        write_tar(
+            flate2::write::GzEncoder::new(
+                File::create(&tgz_path).unwrap(),
+                flate2::Compression::default(),
+            ),
+            FILES,
+        );
+        let zip_path = dir.path().join("proj.zip");
+        write_zip(&zip_path, FILES);
+
+        let walker = walker(ArchiveConfig::default());
+        for archive in [&tar_path, &tgz_path, &zip_path] {
+            let entries = entries(archive, &walker).unwrap();
+            let paths: Vec<&Path> = entries.iter().map(|e| e.path.as_path()).collect();
+            assert_eq!(
+                paths,
+                vec![
+                    Path::new("proj/big.txt"),
+                    Path::new("proj/logo.png"),
+                    Path::new("proj/src/main.rs")
+                ],
+                "{:?}",
+                archive
+            );
+            assert!(entries[0].is_oversized);
+            assert!(entries[1].is_binary);
+            assert_eq!(entries[2].absolute_path, archive.join("proj/src/main.rs"));
+            match &entries[2].source {
+                EntrySource::Memory(bytes) => assert_eq!(&bytes[..], b"fn main() {}\n"),
+                _ => panic!("archive members are read into memory"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsafe_paths_skipped() {
+        assert_eq!(
+            member_path(Path::new("./a/b.rs")),
+            Some(PathBuf::from("a/b.rs"))
+        );
+        assert_eq!(member_path(Path::new("a/../../b.rs")), None);
+        assert_eq!(member_path(Path::new("/etc/passwd")), None);
+        assert_eq!(member_path(Path::new("./")), None);
+
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("evil.zip");
+        write_zip(
+            &archive,
+            &[
+                ("../../outside.sh", b"rm -rf ~"),
+                ("/etc/passwd", b"root"),
+                ("ok.txt", b"fine"),
+            ],
+        );
+        let entries = entries(&archive, &walker(ArchiveConfig::default())).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, Path::new("ok.txt"));
+    }
+
+    #[test]
+    fn test_bomb_guards() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("bomb.tar.gz");
+        let zeros = vec![0u8; 4 << 20];
+        write_tar(
+            flate2::write::GzEncoder::new(
+                File::create(&archive).unwrap(),
+                flate2::Compression::best(),
+            ),
+            &[("zeros.bin", &zeros)],
+        );
+        assert!(fs::metadata(&archive).unwrap().len() < 64 << 10);
+
+        let error = entries(&archive, &walker(ArchiveConfig::default()))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("unpacks to more than"), "{}", error);
+
+        let lenient = ArchiveConfig {
+            max_ratio: 10_000,
+            ..ArchiveConfig::default()
+        };
+        assert_eq!(entries(&archive, &walker(lenient)).unwrap().len(), 1);
+
+        let few = ArchiveConfig {
+            max_entries: 2,
+            ..ArchiveConfig::default()
+        };
+        let archive = dir.path().join("many.zip");
+        write_zip(&archive, FILES);
+        let error = entries(&archive, &walker(few)).unwrap_err().to_string();
+        assert!(error.contains("more than 2 members"), "{}", error);
+    }
+}
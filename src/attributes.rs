@@ -0,0 +1,139 @@
+use crate::config::{AttributeAction, GitAttributesConfig};
+use crate::walker::FileEntry;
+use git2::{AttrCheckFlags, AttrValue, Repository};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Looks up the `.gitattributes` nomnom acts on for files in a repository
+pub struct AttributeRules {
+    repo: Repository,
+    /// Attribute names paired with what to do with files that set them, in
+    /// order of precedence
+    actions: Vec<(&'static str, AttributeAction)>,
+}
+
+impl AttributeRules {
+    /// Opens the repository containing `source`; `None` when there is none or
+    /// every attribute is configured as `include`
+    pub fn discover(source: &Path, config: &GitAttributesConfig) -> Option<Self> {
+        let actions: Vec<(&'static str, AttributeAction)> = [
+            ("export-ignore", config.export_ignore),
+            ("linguist-generated", config.linguist_generated),
+            ("linguist-vendored", config.linguist_vendored),
+        ]
+        .into_iter()
+        .filter(|(_, action)| *action != AttributeAction::Include)
+        .collect();
+        if actions.is_empty() {
+            return None;
+        }
+        let repo = Repository::discover(source).ok()?;
+        repo.workdir()?;
+        Some(Self { repo, actions })
+    }
+
+    /// The repository working directory, canonicalized
+    pub fn workdir(&self) -> Option<PathBuf> {
+        self.repo.workdir()?.canonicalize().ok()
+    }
+
+    /// What to do with a file, by its path relative to the repository root,
+    /// and the attribute that decided it; `None` means include it
+    pub fn action(&self, path: &Path) -> Option<(&'static str, AttributeAction)> {
+        let mut decided = None;
+        for &(name, action) in &self.actions {
+            if !self.is_set(path, name) {
+                continue;
+            }
+            if action == AttributeAction::Skip {
+                return Some((name, action));
+            }
+            decided = decided.or(Some((name, action)));
+        }
+        decided
+    }
+
+    /// Applies the configured action to an entry, returning `false` when it
+    /// should be skipped
+    pub fn apply(&self, entry: &mut FileEntry, path: &Path) -> bool {
+        match self.action(path) {
+            Some((name, AttributeAction::Skip)) => {
+                info!("Filter applied: Skipped by {} - {:?}", name, entry.path);
+                false
+            }
+            Some((name, _)) => {
+                info!("Filter applied: Stubbed by {} - {:?}", name, entry.path);
+                entry.omitted = Some(format!("[omitted: {}]", name));
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn is_set(&self, path: &Path, name: &str) -> bool {
+        let value = match self
+            .repo
+            .get_attr_bytes(path, name, AttrCheckFlags::FILE_THEN_INDEX)
+        {
+            Ok(value) => value,
+            Err(e) => {
+                debug!("Cannot read attribute {} for {:?}: {}", name, path, e);
+                return false;
+            }
+        };
+        // linguist-* also accept an explicit `=true`
+        match AttrValue::from_bytes(value) {
+            AttrValue::True => true,
+            AttrValue::String(value) => value == "true",
+            AttrValue::Bytes(value) => value == b"true",
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_attribute_actions() {
+        let dir = TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+        fs::write(
+            dir.path().join(".gitattributes"),
+            "*.pb.go linguist-generated\n\
+             vendor/** linguist-vendored\n\
+             docs/** export-ignore\n\
+             docs/keep.md linguist-generated=true\n\
+             src/real.pb.go -linguist-generated\n",
+        )
+        .unwrap();
+
+        let rules = AttributeRules::discover(dir.path(), &GitAttributesConfig::default()).unwrap();
+        let action = |path: &str| rules.action(Path::new(path));
+        assert_eq!(
+            action("api/user.pb.go"),
+            Some(("linguist-generated", AttributeAction::Stub))
+        );
+        assert_eq!(
+            action("vendor/jquery.js"),
+            Some(("linguist-vendored", AttributeAction::Stub))
+        );
+        // Skipping wins over stubbing
+        assert_eq!(
+            action("docs/keep.md"),
+            Some(("export-ignore", AttributeAction::Skip))
+        );
+        assert_eq!(action("src/real.pb.go"), None);
+        assert_eq!(action("src/main.rs"), None);
+
+        let include_all = GitAttributesConfig {
+            export_ignore: AttributeAction::Include,
+            linguist_generated: AttributeAction::Include,
+            linguist_vendored: AttributeAction::Include,
+        };
+        assert!(AttributeRules::discover(dir.path(), &include_all).is_none());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{
+    error::{NomnomError, Result},
+    output::OutputWriter,
+    processor::{FileContent, ProcessedFile},
+    tokenizer::Tokenizer,
+};
+use std::path::Path;
+use tracing::debug;
+
+/// File names that are ranked ahead of everything else when packing, since they
+/// usually explain what the rest of the tree is for
+const PRIORITY_FILES: &[&str] = &[
+    "readme",
+    "readme.md",
+    "readme.txt",
+    "readme.rst",
+    "cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "gemfile",
+    "composer.json",
+    "makefile",
+    "dockerfile",
+];
+
+/// A file that was downgraded to a stub while packing
+#[derive(Debug, Clone)]
+pub struct DroppedFile {
+    pub path: String,
+    /// Estimated tokens the full content would have added
+    pub tokens: usize,
+    pub reason: String,
+}
+
+/// Summary of a packing run
+#[derive(Debug, Clone)]
+pub struct BudgetReport {
+    pub max_tokens: usize,
+    pub used_tokens: usize,
+    pub included: usize,
+    pub dropped: Vec<DroppedFile>,
+}
+
+/// Packs processed files into a token budget
+///
+/// Text files are ranked (project manifests and READMEs first, then shallower
+/// paths, then cheaper files) and included in full while the budget allows.
+/// Everything else is replaced with a stub holding its path and size, so the
+/// directory tree stays complete. The packed output is re-rendered and checked
+/// against `max_tokens` with the same tokenizer before it is returned.
+///
+/// `sizes` holds the on-disk size of each entry in `files`.
+pub fn pack(
+    files: Vec<ProcessedFile>,
+    sizes: &[u64],
+    writer: &dyn OutputWriter,
+    tokenizer: &Tokenizer,
+    max_tokens: usize,
+) -> Result<(Vec<ProcessedFile>, BudgetReport)> {
+    let stubs: Vec<ProcessedFile> = files
+        .iter()
+        .zip(sizes)
+        .map(|(file, size)| stub_for(file, *size))
+        .collect();
+
+    let base_tokens = tokenizer.count(&writer.write_output(&stubs)?);
+    if base_tokens > max_tokens {
+        return Err(NomnomError::TokenBudget(format!(
+            "directory tree and stubs alone need ~{} tokens, budget is {}",
+            base_tokens, max_tokens
+        )));
+    }
+
+    // Marginal cost of upgrading each text file from its stub to full content
+    let mut candidates = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        if !matches!(file.content, FileContent::Text(_)) {
+            continue;
+        }
+        let full = tokenizer.count(&writer.write_output(std::slice::from_ref(file))?);
+        let stub = tokenizer.count(&writer.write_output(std::slice::from_ref(&stubs[i]))?);
+        candidates.push((i, full.saturating_sub(stub)));
+    }
+    candidates.sort_by_key(|&(i, cost)| rank_key(&files[i].path, cost));
+
+    let mut included = vec![false; files.len()];
+    let mut upgraded = Vec::new();
+    let mut dropped = Vec::new();
+    let mut used = base_tokens;
+
+    for (i, cost) in candidates {
+        if used + cost <= max_tokens {
+            used += cost;
+            included[i] = true;
+            upgraded.push((i, cost));
+        } else {
+            dropped.push(DroppedFile {
+                path: files[i].path.clone(),
+                tokens: cost,
+                reason: format!(
+                    "would exceed budget ({} tokens left)",
+                    max_tokens.saturating_sub(used)
+                ),
+            });
+        }
+    }
+
+    // Per-file costs don't add up exactly (tokens can merge across section
+    // boundaries), so verify the real total and back out the lowest-ranked
+    // inclusions until it fits
+    loop {
+        let packed = assemble(&files, &stubs, &included);
+        let total = tokenizer.count(&writer.write_output(&packed)?);
+        if total <= max_tokens {
+            debug!(
+                "Packed {} files into ~{} of {} tokens",
+                upgraded.len(),
+                total,
+                max_tokens
+            );
+            let report = BudgetReport {
+                max_tokens,
+                used_tokens: total,
+                included: upgraded.len(),
+                dropped,
+            };
+            return Ok((packed, report));
+        }
+
+        let Some((i, cost)) = upgraded.pop() else {
+            // Unreachable in practice: the all-stub render was checked above
+            return Err(NomnomError::TokenBudget(format!(
+                "output needs ~{} tokens, budget is {}",
+                total, max_tokens
+            )));
+        };
+        included[i] = false;
+        dropped.push(DroppedFile {
+            path: files[i].path.clone(),
+            tokens: cost,
+            reason: "trimmed after final budget check".to_string(),
+        });
+    }
+}
+
+/// Builds the packed file list, taking full content where `included` is set
+fn assemble(
+    files: &[ProcessedFile],
+    stubs: &[ProcessedFile],
+    included: &[bool],
+) -> Vec<ProcessedFile> {
+    files
+        .iter()
+        .zip(stubs)
+        .zip(included)
+        .map(|((file, stub), &keep)| if keep { file.clone() } else { stub.clone() })
+        .collect()
+}
+
+fn stub_for(file: &ProcessedFile, size: u64) -> ProcessedFile {
+    match file.content {
+        FileContent::Text(_) => ProcessedFile {
+            path: file.path.clone(),
+            content: FileContent::Omitted(format!("[omitted: {} bytes]", size)),
+            change: None,
+            submodule: file.submodule.clone(),
+            line_numbers: None,
+        },
+        // Binary, oversized and error entries are already stubs
+        _ => file.clone(),
+    }
+}
+
+fn rank_key(path: &str, cost: usize) -> (u8, usize, usize) {
+    let path = Path::new(path);
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let tier = if PRIORITY_FILES.contains(&name.as_str()) {
+        0
+    } else {
+        1
+    };
+    (tier, path.components().count(), cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{JsonWriter, MarkdownWriter};
+
+    fn text(path: &str, content: &str) -> ProcessedFile {
+        ProcessedFile {
+            path: path.to_string(),
+            content: FileContent::Text(content.to_string()),
+            change: None,
+            submodule: None,
+            line_numbers: None,
+        }
+    }
+
+    fn create_test_files() -> (Vec<ProcessedFile>, Vec<u64>) {
+        let files = vec![
+            text("README.md", "# Project\n\nShort readme."),
+            text("src/big.rs", &"fn filler() {}\n".repeat(200)),
+            text("src/main.rs", "fn main() {}"),
+            ProcessedFile {
+                path: "logo.png".to_string(),
+                content: FileContent::Binary("[binary skipped]".to_string()),
+                change: None,
+                submodule: None,
+                line_numbers: None,
+            },
+        ];
+        let sizes = files
+            .iter()
+            .map(|f| match &f.content {
+                FileContent::Text(t) => t.len() as u64,
+                _ => 1024,
+            })
+            .collect();
+        (files, sizes)
+    }
+
+    #[test]
+    fn test_pack_everything_fits() -> Result<()> {
+        let (files, sizes) = create_test_files();
+        let tokenizer = Tokenizer::Heuristic;
+        let (packed, report) = pack(
+            files,
+            &sizes,
+            &MarkdownWriter::default(),
+            &tokenizer,
+            100_000,
+        )?;
+
+        assert_eq!(report.included, 3);
+        assert!(report.dropped.is_empty());
+        assert!(packed
+            .iter()
+            .all(|f| !matches!(f.content, FileContent::Omitted(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_stubs_files_over_budget() -> Result<()> {
+        let (files, sizes) = create_test_files();
+        let tokenizer = Tokenizer::Heuristic;
+        let max_tokens = 250;
+        let (packed, report) = pack(
+            files,
+            &sizes,
+            &MarkdownWriter::default(),
+            &tokenizer,
+            max_tokens,
+        )?;
+
+        let output = MarkdownWriter::default().write_output(&packed)?;
+        assert!(tokenizer.count(&output) <= max_tokens);
+        assert_eq!(report.used_tokens, tokenizer.count(&output));
+
+        // The large file is dropped but still listed in the tree
+        assert_eq!(report.dropped.len(), 1);
+        assert_eq!(report.dropped[0].path, "src/big.rs");
+        assert!(output.contains("- big.rs"));
+        assert!(output.contains("[omitted: 3000 bytes]"));
+        assert!(output.contains("Short readme."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_never_exceeds_budget() -> Result<()> {
+        let (files, sizes) = create_test_files();
+        let stubs: Vec<_> = files
+            .iter()
+            .zip(&sizes)
+            .map(|(f, s)| stub_for(f, *s))
+            .collect();
+
+        for (tokenizer, step) in [
+            (Tokenizer::Heuristic, 1),
+            (Tokenizer::from_name("cl100k")?, 13),
+        ] {
+            let floor = tokenizer.count(&JsonWriter::default().write_output(&stubs)?);
+            for max_tokens in (floor..floor + 1200).step_by(step) {
+                let (packed, _) = pack(
+                    files.clone(),
+                    &sizes,
+                    &JsonWriter::default(),
+                    &tokenizer,
+                    max_tokens,
+                )?;
+                let output = JsonWriter::default().write_output(&packed)?;
+                assert!(tokenizer.count(&output) <= max_tokens);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_pack_budget_too_small() {
+        let (files, sizes) = create_test_files();
+        let result = pack(
+            files,
+            &sizes,
+            &MarkdownWriter::default(),
+            &Tokenizer::Heuristic,
+            10,
+        );
+        assert!(matches!(result, Err(NomnomError::TokenBudget(_))));
+    }
+}
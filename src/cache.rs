@@ -0,0 +1,279 @@
+use crate::auth::redact_url;
+use crate::error::{NomnomError, Result};
+use git2::{Oid, Repository};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
+
+/// Marker file touched each time a mirror is used, for `cache prune`
+const LAST_USED_FILE: &str = "nomnom-last-used";
+
+/// A directory of bare mirrors of remote repositories, keyed by URL
+#[derive(Debug, Clone)]
+pub struct CloneCache {
+    dir: PathBuf,
+}
+
+/// A cached mirror as shown by `nomnom cache list`
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub url: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: Option<SystemTime>,
+}
+
+impl CloneCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// The cache under the user cache directory (e.g. `~/.cache/nomnom/git`)
+    pub fn default_location() -> Result<Self> {
+        let dir = dirs::cache_dir()
+            .ok_or_else(|| NomnomError::Cache("No user cache directory available".to_string()))?;
+        Ok(Self::new(dir.join("nomnom").join("git")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the bare mirror for a URL
+    pub fn mirror_path(&self, url: &str) -> PathBuf {
+        self.dir.join(mirror_key(url))
+    }
+
+    /// Opens the mirror for a URL, creating an empty one unless `offline`
+    pub fn open_mirror(&self, url: &str, offline: bool) -> Result<Repository> {
+        let path = self.mirror_path(url);
+        let repo = if path.exists() {
+            debug!("Using cached mirror: {:?}", path);
+            Repository::open_bare(&path)?
+        } else if offline {
+            return Err(NomnomError::Cache(format!(
+                "No cached mirror for '{}' (run once without --offline to populate it)",
+                redact_url(url)
+            )));
+        } else {
+            info!("Creating cached mirror: {:?}", path);
+            fs::create_dir_all(&self.dir)?;
+            let repo = Repository::init_bare(&path)?;
+            repo.remote("origin", url)?;
+            repo
+        };
+        fs::write(path.join(LAST_USED_FILE), b"")?;
+        Ok(repo)
+    }
+
+    /// Lists cached mirrors, most recently used first
+    pub fn list(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e.into()),
+        };
+
+        for dir_entry in read_dir {
+            let path = dir_entry?.path();
+            let Ok(repo) = Repository::open_bare(&path) else {
+                debug!("Skipping non-repository in cache: {:?}", path);
+                continue;
+            };
+            let url = repo
+                .find_remote("origin")
+                .ok()
+                .and_then(|remote| remote.url().map(str::to_string))
+                .unwrap_or_default();
+            let last_used = fs::metadata(path.join(LAST_USED_FILE))
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            entries.push(CacheEntry {
+                url,
+                size: directory_size(&path),
+                path,
+                last_used,
+            });
+        }
+
+        entries.sort_by(|a, b| b.last_used.cmp(&a.last_used).then(a.url.cmp(&b.url)));
+        Ok(entries)
+    }
+
+    /// Removes mirrors not used within `max_age` (all mirrors when `None`),
+    /// returning the removed entries
+    pub fn prune(&self, max_age: Option<Duration>) -> Result<Vec<CacheEntry>> {
+        let now = SystemTime::now();
+        let mut removed = Vec::new();
+        for entry in self.list()? {
+            let expired = match (max_age, entry.last_used) {
+                (None, _) | (Some(_), None) => true,
+                (Some(max_age), Some(last_used)) => {
+                    now.duration_since(last_used).is_ok_and(|age| age > max_age)
+                }
+            };
+            if expired {
+                debug!("Removing cached mirror: {:?}", entry.path);
+                fs::remove_dir_all(&entry.path)?;
+                removed.push(entry);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Makes a mirror's objects and refs visible in `repo` without copying them,
+/// like `git clone --shared`
+pub fn share_mirror(mirror: &Repository, repo: &Repository) -> Result<()> {
+    let objects = mirror.path().join("objects");
+    let info = repo.path().join("objects").join("info");
+    fs::create_dir_all(&info)?;
+    fs::write(
+        info.join("alternates"),
+        format!("{}\n", objects.to_string_lossy()),
+    )?;
+
+    // Shallow boundaries must travel with the objects or history walks fail
+    let shallow = mirror.path().join("shallow");
+    if shallow.exists() {
+        fs::copy(&shallow, repo.path().join("shallow"))?;
+    }
+
+    for reference in mirror.references()? {
+        let reference = reference?;
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        if let Some(target) = reference.symbolic_target() {
+            repo.reference_symbolic(name, target, true, "nomnom: shared from cache")?;
+        } else if let Some(oid) = reference.target() {
+            repo.reference(name, oid, true, "nomnom: shared from cache")?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolves a reference from what is already in the mirror, without network access
+pub fn resolve_offline(mirror: &Repository, reference: Option<&str>) -> Result<Oid> {
+    let Some(reference) = reference else {
+        return Ok(mirror
+            .find_reference("refs/remotes/origin/HEAD")
+            .map_err(|_| {
+                NomnomError::Cache(
+                    "Cached mirror has no default branch recorded (specify a reference)"
+                        .to_string(),
+                )
+            })?
+            .peel_to_commit()?
+            .id());
+    };
+
+    let candidates = [
+        format!("refs/remotes/origin/{}", reference),
+        format!("refs/tags/{}", reference),
+        reference.to_string(),
+    ];
+    for candidate in &candidates {
+        if let Ok(found) = mirror.find_reference(candidate) {
+            return Ok(found.peel_to_commit()?.id());
+        }
+    }
+
+    mirror
+        .revparse_single(reference)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|_| {
+            NomnomError::Cache(format!(
+                "Reference '{}' is not in the cache (run without --offline to fetch it)",
+                reference
+            ))
+        })
+}
+
+/// Directory name for a URL: a readable slug plus a hash so distinct URLs
+/// that slug the same never share a mirror
+fn mirror_key(url: &str) -> String {
+    let slug: String = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // FNV-1a: stable across releases, unlike std's DefaultHasher
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{}-{:016x}.git", slug, hash)
+}
+
+fn directory_size(path: &Path) -> u64 {
+    let Ok(read_dir) = fs::read_dir(path) else {
+        return 0;
+    };
+    read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_mirror_key() {
+        let key = mirror_key("https://github.com/user/repo.git");
+        assert!(key.starts_with("repo-"));
+        assert!(key.ends_with(".git"));
+        assert_eq!(key, mirror_key("https://github.com/user/repo.git"));
+        assert_ne!(key, mirror_key("https://gitlab.com/user/repo.git"));
+        assert!(mirror_key("git@github.com:user/my.repo.git").starts_with("my_repo-"));
+    }
+
+    #[test]
+    fn test_list_and_prune() {
+        let dir = TempDir::new().unwrap();
+        let cache = CloneCache::new(dir.path().join("git"));
+        assert!(cache.list().unwrap().is_empty());
+
+        assert!(cache
+            .open_mirror("https://example.com/a.git", true)
+            .is_err());
+        cache
+            .open_mirror("https://example.com/a.git", false)
+            .unwrap();
+        cache
+            .open_mirror("https://example.com/b.git", false)
+            .unwrap();
+
+        let entries = cache.list().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.last_used.is_some() && e.size > 0));
+
+        // Freshly used mirrors survive an age-limited prune
+        let removed = cache.prune(Some(Duration::from_secs(3600))).unwrap();
+        assert!(removed.is_empty());
+
+        let removed = cache.prune(None).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(cache.list().unwrap().is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -36,13 +36,122 @@ pub struct Cli {
     #[arg(long)]
     pub validate_config: bool,
 
+    /// Only include files matching this gitignore-style glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Exclude files and directories matching this gitignore-style glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only dump files changed in a git range (e.g. main..HEAD, main...HEAD), with their diffs
+    #[arg(long, value_name = "RANGE", conflicts_with = "since")]
+    pub diff: Option<String>,
+
+    /// Only dump files changed since a git ref, including uncommitted changes
+    #[arg(long, value_name = "REF")]
+    pub since: Option<String>,
+
+    /// Only walk files in the git index
+    #[arg(long, conflicts_with_all = ["dirty", "diff", "since"])]
+    pub tracked_only: bool,
+
+    /// Only walk modified, staged and untracked files, as listed by git status
+    #[arg(long, conflicts_with_all = ["diff", "since"])]
+    pub dirty: bool,
+
+    /// Process the files listed in PATH ('-' for stdin), one per line or NUL-separated, instead of walking a source
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["source", "diff", "since", "tracked_only", "dirty"]
+    )]
+    pub files_from: Option<String>,
+
+    /// Number of commits to fetch when cloning a remote repository
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub depth: u32,
+
+    /// Fetch the full history when cloning a remote repository
+    #[arg(long, conflicts_with = "depth")]
+    pub full_history: bool,
+
+    /// Recursively check out submodules when cloning a remote repository
+    #[arg(long)]
+    pub submodules: bool,
+
+    /// Add a section with the last N commits touching the selected files
+    #[arg(long, value_name = "N")]
+    pub git_log: Option<usize>,
+
+    /// Add each file's last-modified commit and top contributors
+    #[arg(long)]
+    pub blame_summary: bool,
+
+    /// Keep remote repositories in a persistent clone cache and fetch only what changed
+    #[arg(long)]
+    pub cache: bool,
+
+    /// Use the clone cache without network access (implies --cache)
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Reduce source files to their declarations, with function bodies collapsed to { … }
+    #[arg(long)]
+    pub outline: bool,
+
+    /// Number the lines of file content as in the original files
+    #[arg(long)]
+    pub line_numbers: bool,
+
+    /// Maximum output tokens; files that don't fit are replaced with stubs
+    #[arg(long)]
+    pub max_tokens: Option<usize>,
+
+    /// Split output into numbered parts of at most N tokens each (requires --out)
+    #[arg(long, conflicts_with = "split_size")]
+    pub split_tokens: Option<usize>,
+
+    /// Split output into numbered parts of at most SIZE bytes each (supports K/M/G suffix)
+    #[arg(long)]
+    pub split_size: Option<String>,
+
+    /// Tokenizer for token counts: heuristic, cl100k, o200k, or a vocabulary file path
+    #[arg(long)]
+    pub tokenizer: Option<String>,
+
     /// Disable safe logging (shows actual secret values in logs - use with caution)
     #[arg(long)]
     pub unsafe_logging: bool,
 
-    /// Source file, directory, or remote git URL to process
+    /// Source files, directories, or remote git URLs to process; several are
+    /// listed side by side under named roots
     #[arg(default_value = ".")]
-    pub source: String,
+    pub source: Vec<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage the clone cache of remote repositories
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// List cached repositories
+    List,
+    /// Remove cached repositories
+    Prune {
+        /// Only remove repositories not used for this many days
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+    },
 }
 
 #[derive(Clone, Debug, ValueEnum)]
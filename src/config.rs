@@ -4,21 +4,75 @@ use figment::{
     Figment,
 };
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 fn default_safe_logging() -> bool {
     true // Default to safe logging to prevent accidental secret leakage
 }
 
+fn default_tokenizer() -> String {
+    "cl100k".to_string()
+}
+
+fn default_oversized_lines() -> usize {
+    400
+}
+
+fn default_oversized_bytes() -> String {
+    "64K".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub threads: ThreadsConfig,
     pub max_size: String,
+    /// What to output for files over `max_size`
+    #[serde(default)]
+    pub oversized_strategy: OversizedStrategy,
+    /// Most lines kept from an oversized file, across all its excerpts
+    #[serde(default = "default_oversized_lines")]
+    pub oversized_lines: usize,
+    /// Most bytes kept from an oversized file, across all its excerpts
+    #[serde(default = "default_oversized_bytes")]
+    pub oversized_bytes: String,
+    /// Reduce source files to their declarations, collapsing bodies
+    #[serde(default)]
+    pub outline: bool,
+    /// Number the lines of text content as in the original file
+    #[serde(default)]
+    pub line_numbers: bool,
+    /// What to strip from files, by language name as in Markdown code fences,
+    /// with `"*"` for languages without an entry of their own
+    #[serde(default)]
+    pub strip: BTreeMap<String, StripConfig>,
     pub format: String,
     pub ignore_git: bool,
+    /// Gitignore-style globs; when set, only matching files are walked
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Gitignore-style globs for files and directories to skip
+    #[serde(default)]
+    pub exclude: Vec<String>,
     pub filters: Vec<FilterConfig>,
+    /// What to do with files marked in `.gitattributes`
+    #[serde(default)]
+    pub gitattributes: GitAttributesConfig,
+    /// Limits on reading tar and zip archives
+    #[serde(default)]
+    pub archives: ArchiveConfig,
+    /// Keep remote repositories in the persistent clone cache
+    #[serde(default)]
+    pub git_cache: bool,
+    /// SSH private keys to try for remote repositories, before `~/.ssh/id_*`
+    #[serde(default)]
+    pub ssh_keys: Vec<PathBuf>,
     #[serde(default = "default_safe_logging")]
     pub safe_logging: bool,
+    #[serde(default = "default_tokenizer")]
+    pub tokenizer: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,20 +82,156 @@ pub enum ThreadsConfig {
     Count(u32),
 }
 
+/// How much of a file over `max_size` ends up in the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedStrategy {
+    /// Only a `[file too large]` placeholder
+    #[default]
+    Stub,
+    /// The first lines
+    Head,
+    /// The first and last lines
+    HeadTail,
+    /// Evenly spaced excerpts from start to end
+    Sample,
+}
+
+impl OversizedStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OversizedStrategy::Stub => "stub",
+            OversizedStrategy::Head => "head",
+            OversizedStrategy::HeadTail => "head_tail",
+            OversizedStrategy::Sample => "sample",
+        }
+    }
+}
+
+/// What to do with a file that sets a `.gitattributes` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeAction {
+    /// Leave the file out of the output
+    Skip,
+    /// List the file with a placeholder instead of its content
+    Stub,
+    /// Treat the file like any other
+    Include,
+}
+
+impl AttributeAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttributeAction::Skip => "skip",
+            AttributeAction::Stub => "stub",
+            AttributeAction::Include => "include",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitAttributesConfig {
+    /// Files excluded from `git archive`
+    pub export_ignore: AttributeAction,
+    /// Files GitHub Linguist treats as generated
+    pub linguist_generated: AttributeAction,
+    /// Files GitHub Linguist treats as third-party code
+    pub linguist_vendored: AttributeAction,
+}
+
+impl Default for GitAttributesConfig {
+    fn default() -> Self {
+        Self {
+            export_ignore: AttributeAction::Skip,
+            linguist_generated: AttributeAction::Stub,
+            linguist_vendored: AttributeAction::Stub,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Also read archives found while walking a directory, not just archive sources
+    pub expand_nested: bool,
+    /// Most bytes one archive may unpack to (supports K/M/G suffix)
+    pub max_unpacked_size: String,
+    /// Most files one archive may contain
+    pub max_entries: usize,
+    /// Most a compressed archive may unpack to, as a multiple of its own size
+    pub max_ratio: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            expand_nested: false,
+            max_unpacked_size: "256M".to_string(),
+            max_entries: 10_000,
+            max_ratio: 100,
+        }
+    }
+}
+
+/// What the strip stage removes from a language's files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StripConfig {
+    /// Comments running to the end of the line, like `// …` and `# …`
+    pub line_comments: bool,
+    /// Comments like `/* … */` and `<!-- … -->`
+    pub block_comments: bool,
+    /// Doc comments like `/// …` and `/** … */`
+    pub doc_comments: bool,
+    /// Leading blank lines, and all but the first of a run of blank lines
+    pub blank_lines: bool,
+    /// Spaces and tabs at the end of lines
+    pub trailing_whitespace: bool,
+}
+
+impl Default for StripConfig {
+    fn default() -> Self {
+        Self {
+            line_comments: true,
+            block_comments: true,
+            doc_comments: false,
+            blank_lines: true,
+            trailing_whitespace: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilterConfig {
     pub r#type: String,
+    /// What the filter matches; unused by `entropy` filters
+    #[serde(default)]
     pub pattern: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_pattern: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub threshold: Option<u32>,
+    /// Characters an `entropy` filter's candidate strings are made of: a
+    /// named set or a regex character class
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+    /// Shortest candidate string an `entropy` filter measures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// Shannon entropy, in bits per character, from which an `entropy`
+    /// filter redacts a candidate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_entropy: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ConfigValidation {
     pub config: Config,
     pub discovered_files: Vec<ConfigFile>,
+    /// Ignore files that apply to the source; empty for remote sources
+    pub ignore_files: Vec<PathBuf>,
     pub validation_errors: Vec<String>,
     pub validation_warnings: Vec<String>,
 }
@@ -58,8 +248,16 @@ impl Default for Config {
         Self {
             threads: ThreadsConfig::Auto("auto".to_string()),
             max_size: "4M".to_string(),
+            oversized_strategy: OversizedStrategy::default(),
+            oversized_lines: default_oversized_lines(),
+            oversized_bytes: default_oversized_bytes(),
+            outline: false,
+            line_numbers: false,
+            strip: BTreeMap::new(),
             format: "md".to_string(),
             ignore_git: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
             filters: vec![
                 // Conservative redaction filters - catch obvious secrets without false positives
                 FilterConfig {
@@ -67,39 +265,62 @@ impl Default for Config {
                     pattern: r"(?i)(password|api[_-]?key)\s*[:=]\s*\S+".to_string(),
                     file_pattern: None,
                     threshold: None,
+                    charset: None,
+                    min_length: None,
+                    min_entropy: None,
                 },
                 FilterConfig {
                     r#type: "redact".to_string(),
                     pattern: r"\bAKIA[0-9A-Z]{16}\b".to_string(),
                     file_pattern: None,
                     threshold: None,
+                    charset: None,
+                    min_length: None,
+                    min_entropy: None,
                 },
                 FilterConfig {
                     r#type: "redact".to_string(),
                     pattern: r"(?i)(secret|token)\s*[:=]\s*[A-Za-z0-9+/]{20,}={0,2}".to_string(),
                     file_pattern: None,
                     threshold: None,
+                    charset: None,
+                    min_length: None,
+                    min_entropy: None,
                 },
                 FilterConfig {
                     r#type: "truncate".to_string(),
                     pattern: r"<style[^>]*>.*?</style>".to_string(),
                     file_pattern: Some(r"\.html?$".to_string()),
                     threshold: None,
+                    charset: None,
+                    min_length: None,
+                    min_entropy: None,
                 },
                 FilterConfig {
                     r#type: "truncate".to_string(),
                     pattern: r"<svg[^>]*>.*?</svg>".to_string(),
                     file_pattern: Some(r"\.(html?|xml|svg)$".to_string()),
                     threshold: None,
+                    charset: None,
+                    min_length: None,
+                    min_entropy: None,
                 },
                 FilterConfig {
                     r#type: "truncate".to_string(),
                     pattern: r#""[^"]{100,}""#.to_string(),
                     file_pattern: Some(r"\.json$".to_string()),
                     threshold: Some(50),
+                    charset: None,
+                    min_length: None,
+                    min_entropy: None,
                 },
             ],
+            gitattributes: GitAttributesConfig::default(),
+            archives: ArchiveConfig::default(),
+            git_cache: false,
+            ssh_keys: Vec::new(),
             safe_logging: default_safe_logging(),
+            tokenizer: default_tokenizer(),
         }
     }
 }
@@ -159,9 +380,29 @@ impl Config {
         parse_size(&self.max_size)
     }
 
+    /// Checks the limits on excerpts of oversized files, returning
+    /// `oversized_bytes` parsed
+    pub fn resolve_oversized_bytes(&self) -> Result<u64> {
+        if self.oversized_lines == 0 {
+            return Err(NomnomError::InvalidConfig(
+                "oversized_lines must be at least 1".to_string(),
+            ));
+        }
+        match parse_size(&self.oversized_bytes) {
+            Ok(0) => Err(NomnomError::InvalidConfig(
+                "oversized_bytes must be at least 1".to_string(),
+            )),
+            Ok(bytes) => Ok(bytes),
+            Err(e) => Err(NomnomError::InvalidConfig(format!(
+                "Invalid oversized_bytes: {}",
+                e
+            ))),
+        }
+    }
+
     pub fn load_with_validation(
         extra_config: Option<PathBuf>,
-        _cli: &crate::cli::Cli,
+        cli: &crate::cli::Cli,
     ) -> Result<ConfigValidation> {
         let mut discovered_files = Vec::new();
         let mut validation_errors = Vec::new();
@@ -217,6 +458,37 @@ impl Config {
             validation_errors.push(format!("Invalid max_size: {}", e));
         }
 
+        if let Err(e) = config.resolve_oversized_bytes() {
+            validation_errors.push(e.to_string());
+        }
+
+        if let Err(e) = parse_size(&config.archives.max_unpacked_size) {
+            validation_errors.push(format!("Invalid archives.max_unpacked_size: {}", e));
+        }
+
+        if let Err(e) = crate::walker::Walker::new(config.clone()).overrides(Path::new(".")) {
+            validation_errors.push(format!("Invalid include/exclude glob: {}", e));
+        }
+
+        let mut ignore_files = Vec::new();
+        for source in &cli.source {
+            if crate::git::is_remote_source(source) {
+                continue;
+            }
+            match crate::walker::Walker::new(config.clone()).ignore_files(source) {
+                Ok(files) => ignore_files.extend(files),
+                Err(e) => validation_warnings.push(format!("Cannot list ignore files: {}", e)),
+            }
+        }
+
+        if let Err(e) = crate::filter::FilterEngine::new(&config.filters) {
+            validation_errors.push(e.to_string());
+        }
+
+        if let Err(e) = crate::tokenizer::Tokenizer::from_name(&config.tokenizer) {
+            validation_warnings.push(format!("{} - heuristic token counts will be used", e));
+        }
+
         // Check for potential issues
         if config.filters.is_empty() {
             validation_warnings
@@ -226,6 +498,7 @@ impl Config {
         Ok(ConfigValidation {
             config,
             discovered_files,
+            ignore_files,
             validation_errors,
             validation_warnings,
         })
@@ -0,0 +1,430 @@
+use crate::{
+    attributes::AttributeRules,
+    error::{NomnomError, Result},
+    walker::{is_excluded, EntrySource, FileEntry, Walker},
+};
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, FileMode, Patch, Repository};
+use std::{path::Path, sync::Arc};
+use tracing::{debug, info};
+
+/// Which changes to dump instead of walking the whole tree
+#[derive(Debug, Clone)]
+pub enum DiffSpec {
+    /// `a..b` (or `a...b` for changes since the merge base); a single ref means `ref..HEAD`
+    Range(String),
+    /// Changes from a ref to the working tree, including uncommitted and untracked files
+    Since(String),
+}
+
+/// How a file changed between the two sides of a diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed { from: String },
+    Copied { from: String },
+    TypeChanged,
+}
+
+impl ChangeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeStatus::Added => "added",
+            ChangeStatus::Modified => "modified",
+            ChangeStatus::Deleted => "deleted",
+            ChangeStatus::Renamed { .. } => "renamed",
+            ChangeStatus::Copied { .. } => "copied",
+            ChangeStatus::TypeChanged => "typechange",
+        }
+    }
+
+    /// The original path for renames and copies
+    pub fn old_path(&self) -> Option<&str> {
+        match self {
+            ChangeStatus::Renamed { from } | ChangeStatus::Copied { from } => Some(from),
+            _ => None,
+        }
+    }
+
+    /// Marker shown after the file name in the directory tree
+    pub fn tree_marker(&self) -> Option<String> {
+        match self {
+            ChangeStatus::Deleted => Some("(deleted)".to_string()),
+            ChangeStatus::Renamed { from } => Some(format!("(renamed from {})", from)),
+            _ => None,
+        }
+    }
+}
+
+/// A file's change and its unified diff
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    pub status: ChangeStatus,
+    pub patch: String,
+}
+
+/// Lists the files changed under `source` as walker entries
+///
+/// Paths are relative to the repository root. Files in a commit range are read
+/// from the new side's tree, so the working copy does not need to be checked
+/// out at that commit; `--since` reads the working tree. Include/exclude globs
+/// and the ignore files of the working tree apply as they do to a walk.
+pub fn changed_files(source: &Path, spec: &DiffSpec, walker: &Walker) -> Result<Vec<FileEntry>> {
+    let repo = Repository::discover(source)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| {
+            NomnomError::Diff("diff mode needs a repository with a working tree".into())
+        })?
+        .canonicalize()?;
+    let source = source.canonicalize()?;
+    let subpath = source.strip_prefix(&workdir).unwrap_or(Path::new(""));
+
+    let mut opts = DiffOptions::new();
+    if !subpath.as_os_str().is_empty() {
+        debug!("Limiting diff to: {:?}", subpath);
+        opts.pathspec(subpath);
+    }
+
+    let (mut diff, in_workdir) = match spec {
+        DiffSpec::Range(range) => {
+            let (old, new) = resolve_range(&repo, range)?;
+            info!("Diff range: {}..{}", short_id(&old), short_id(&new));
+            let diff =
+                repo.diff_tree_to_tree(Some(&old.tree()?), Some(&new.tree()?), Some(&mut opts))?;
+            (diff, false)
+        }
+        DiffSpec::Since(reference) => {
+            let old = resolve_commit(&repo, reference)?;
+            info!(
+                "Diff since: {} (including working tree changes)",
+                short_id(&old)
+            );
+            opts.include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+            let diff = repo.diff_tree_to_workdir_with_index(Some(&old.tree()?), Some(&mut opts))?;
+            (diff, true)
+        }
+    };
+    diff.find_similar(Some(
+        DiffFindOptions::new().renames(true).for_untracked(true),
+    ))?;
+
+    let overrides = walker.overrides(&source)?;
+    let ignores = walker.ignore_matcher(&source)?;
+    let attributes = AttributeRules::discover(&workdir, walker.gitattributes());
+    let max_size = walker.max_size()?;
+
+    let mut entries = Vec::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let status = match delta.status() {
+            Delta::Added | Delta::Untracked => ChangeStatus::Added,
+            Delta::Modified | Delta::Conflicted => ChangeStatus::Modified,
+            Delta::Deleted => ChangeStatus::Deleted,
+            Delta::Renamed => ChangeStatus::Renamed {
+                from: git_path(delta.old_file().path()),
+            },
+            Delta::Copied => ChangeStatus::Copied {
+                from: git_path(delta.old_file().path()),
+            },
+            Delta::Typechange => ChangeStatus::TypeChanged,
+            Delta::Unmodified | Delta::Ignored | Delta::Unreadable => continue,
+        };
+
+        let side = if status == ChangeStatus::Deleted {
+            delta.old_file()
+        } else {
+            delta.new_file()
+        };
+        if side.mode() == FileMode::Commit {
+            debug!("Skipping submodule change: {:?}", side.path());
+            continue;
+        }
+        let Some(path) = side.path().map(Path::to_path_buf) else {
+            continue;
+        };
+        let absolute_path = workdir.join(&path);
+
+        if ignores.is_ignored(&absolute_path) {
+            debug!("Ignored changed file: {:?}", path);
+            continue;
+        }
+        if let Some(ref overrides) = overrides {
+            if is_excluded(overrides, &absolute_path, &source) {
+                debug!("Excluded changed file: {:?}", path);
+                continue;
+            }
+        }
+
+        let patch = match Patch::from_diff(&diff, index)? {
+            Some(mut patch) => String::from_utf8_lossy(&patch.to_buf()?).into_owned(),
+            None => String::new(),
+        };
+
+        let (source, size) = if status == ChangeStatus::Deleted {
+            (EntrySource::Memory(Arc::from(&[][..])), 0)
+        } else if in_workdir {
+            let size = std::fs::metadata(&absolute_path)
+                .map(|m| m.len())
+                .unwrap_or(0);
+            (EntrySource::Disk, size)
+        } else {
+            let (size, _) = repo.odb()?.read_header(side.id())?;
+            (
+                EntrySource::Blob {
+                    repo: repo.path().to_path_buf(),
+                    oid: side.id(),
+                },
+                size as u64,
+            )
+        };
+
+        let mut entry = FileEntry {
+            is_binary: walker.is_binary_by_extension(&path),
+            is_oversized: size > max_size,
+            path,
+            absolute_path,
+            size,
+            source,
+            change: Some(FileChange { status, patch }),
+            submodule: None,
+            omitted: None,
+        };
+        if let Some(ref rules) = attributes {
+            let path = entry.path.clone();
+            if !rules.apply(&mut entry, &path) {
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    info!("Found {} changed files", entries.len());
+    Ok(entries)
+}
+
+fn git_path(path: Option<&Path>) -> String {
+    path.map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default()
+}
+
+fn short_id(commit: &Commit) -> String {
+    commit.id().to_string()[..7].to_string()
+}
+
+/// Resolves a ref, falling back to `origin/<ref>` for branches that only
+/// exist on the remote of a fresh clone
+fn resolve_commit<'r>(repo: &'r Repository, spec: &str) -> Result<Commit<'r>> {
+    let spec = if spec.is_empty() { "HEAD" } else { spec };
+    repo.revparse_single(spec)
+        .or_else(|e| {
+            repo.revparse_single(&format!("origin/{}", spec))
+                .map_err(|_| e)
+        })
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| NomnomError::Diff(format!("cannot resolve '{}': {}", spec, e.message())))
+}
+
+fn resolve_range<'r>(repo: &'r Repository, range: &str) -> Result<(Commit<'r>, Commit<'r>)> {
+    if let Some((old, new)) = range.split_once("...") {
+        let old = resolve_commit(repo, old)?;
+        let new = resolve_commit(repo, new)?;
+        let base = repo.merge_base(old.id(), new.id())?;
+        Ok((repo.find_commit(base)?, new))
+    } else if let Some((old, new)) = range.split_once("..") {
+        Ok((resolve_commit(repo, old)?, resolve_commit(repo, new)?))
+    } else {
+        Ok((resolve_commit(repo, range)?, resolve_commit(repo, "HEAD")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use git2::Signature;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_all(repo: &Repository, message: &str) -> git2::Oid {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parents: Vec<Commit> = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<&Commit> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    fn create_repo() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn one() {}\n").unwrap();
+        fs::write(
+            root.join("src/old_name.rs"),
+            "pub const NAME: &str = \"a fairly long constant\";\n",
+        )
+        .unwrap();
+        fs::write(root.join("README.md"), "# Readme\n").unwrap();
+        commit_all(&repo, "initial");
+        repo.tag_lightweight(
+            "v1",
+            &repo.head().unwrap().peel(git2::ObjectType::Commit).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn one() {}\npub fn two() {}\n",
+        )
+        .unwrap();
+        fs::rename(root.join("src/old_name.rs"), root.join("src/new_name.rs")).unwrap();
+        fs::remove_file(root.join("README.md")).unwrap();
+        commit_all(&repo, "second");
+        (dir, repo)
+    }
+
+    fn statuses(entries: &[FileEntry]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|e| {
+                (
+                    e.path.to_string_lossy().to_string(),
+                    e.change.as_ref().unwrap().status.as_str().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_range() -> Result<()> {
+        let (dir, _repo) = create_repo();
+        // The working copy differs from HEAD; range mode reads from the tree
+        fs::write(dir.path().join("src/lib.rs"), "uncommitted\n").unwrap();
+
+        let walker = Walker::new(Config::default());
+        let entries = changed_files(dir.path(), &DiffSpec::Range("v1..HEAD".into()), &walker)?;
+        assert_eq!(
+            statuses(&entries),
+            vec![
+                ("README.md".to_string(), "deleted".to_string()),
+                ("src/lib.rs".to_string(), "modified".to_string()),
+                ("src/new_name.rs".to_string(), "renamed".to_string()),
+            ]
+        );
+
+        let lib = &entries[1];
+        let patch = &lib.change.as_ref().unwrap().patch;
+        assert!(patch.contains("+pub fn two() {}"));
+        match &lib.source {
+            EntrySource::Blob { repo, oid } => {
+                assert!(crate::tree::read_blob(repo, *oid)?.starts_with(b"pub fn one"))
+            }
+            _ => panic!("range content should come from the tree"),
+        }
+
+        let renamed = &entries[2].change.as_ref().unwrap().status;
+        assert_eq!(renamed.old_path(), Some("src/old_name.rs"));
+        assert_eq!(
+            renamed.tree_marker().as_deref(),
+            Some("(renamed from src/old_name.rs)")
+        );
+
+        // A single ref is compared against HEAD
+        let single = changed_files(dir.path(), &DiffSpec::Range("v1".into()), &walker)?;
+        assert_eq!(statuses(&single), statuses(&entries));
+        Ok(())
+    }
+
+    #[test]
+    fn test_since_includes_working_tree() -> Result<()> {
+        let (dir, _repo) = create_repo();
+        fs::write(dir.path().join("src/lib.rs"), "uncommitted\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "untracked\n").unwrap();
+
+        let walker = Walker::new(Config::default());
+        let entries = changed_files(dir.path(), &DiffSpec::Since("HEAD".into()), &walker)?;
+        assert_eq!(
+            statuses(&entries),
+            vec![
+                ("notes.txt".to_string(), "added".to_string()),
+                ("src/lib.rs".to_string(), "modified".to_string()),
+            ]
+        );
+        assert!(matches!(entries[1].source, EntrySource::Disk));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subpath_and_excludes() -> Result<()> {
+        let (dir, _repo) = create_repo();
+        let config = Config {
+            exclude: vec!["new_name.rs".to_string()],
+            ..Config::default()
+        };
+        let walker = Walker::new(config);
+        let entries = changed_files(
+            &dir.path().join("src"),
+            &DiffSpec::Range("v1..HEAD".into()),
+            &walker,
+        )?;
+        assert_eq!(
+            statuses(&entries),
+            vec![("src/lib.rs".to_string(), "modified".to_string())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_excluded_dirs_and_nomnomignore() -> Result<()> {
+        let (dir, _repo) = create_repo();
+        let root = dir.path();
+        fs::create_dir_all(root.join("vendor/dep")).unwrap();
+        fs::write(root.join("vendor/dep/lib.rs"), "vendored\n").unwrap();
+        fs::write(root.join("src/secret.key"), "hunter2\n").unwrap();
+        fs::write(root.join(".nomnomignore"), "*.key\n").unwrap();
+
+        let config = Config {
+            exclude: vec!["vendor".to_string()],
+            ..Config::default()
+        };
+        let entries = changed_files(root, &DiffSpec::Since("HEAD".into()), &Walker::new(config))?;
+        assert_eq!(
+            statuses(&entries),
+            vec![(".nomnomignore".to_string(), "added".to_string())]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_ref() {
+        let (dir, _repo) = create_repo();
+        let walker = Walker::new(Config::default());
+        let err =
+            changed_files(dir.path(), &DiffSpec::Range("nope..HEAD".into()), &walker).unwrap_err();
+        assert!(err.to_string().contains("cannot resolve 'nope'"));
+    }
+}
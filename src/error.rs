@@ -23,6 +23,17 @@ pub enum NomnomError {
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Invalid filter: filters[{index}].{field} '{value}': {reason}")]
+    InvalidFilter {
+        index: usize,
+        field: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
     #[error("Invalid thread count: {0}")]
     InvalidThreadCount(String),
 
@@ -35,6 +46,30 @@ pub enum NomnomError {
     #[error("Binary file detected: {path}")]
     BinaryFile { path: String },
 
+    #[error("Tokenizer error: {0}")]
+    Tokenizer(String),
+
+    #[error("Token budget error: {0}")]
+    TokenBudget(String),
+
+    #[error("Diff error: {0}")]
+    Diff(String),
+
+    #[error("Git status error: {0}")]
+    Status(String),
+
+    #[error("Source error: {0}")]
+    Source(String),
+
+    #[error("Archive error: {0}")]
+    Archive(String),
+
+    #[error("Cache error: {0}")]
+    Cache(String),
+
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+
     #[error("Output error: {0}")]
     Output(String),
 }
@@ -0,0 +1,408 @@
+use crate::{
+    config::OversizedStrategy,
+    error::Result,
+    walker::{EntrySource, FileEntry},
+};
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+/// Evenly spaced windows read by the `sample` strategy, including both ends
+const SAMPLE_WINDOWS: u64 = 5;
+
+/// Bytes read at a time when counting lines in the parts left out
+const COUNT_CHUNK: u64 = 64 * 1024;
+
+/// A run of an oversized file: bytes kept in the output, or the number of
+/// bytes left out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Kept(Vec<u8>),
+    Omitted(u64),
+}
+
+/// Reads the parts of an oversized file that `strategy` keeps, with the gaps
+/// between them, in file order
+///
+/// At most `max_lines` lines and `max_bytes` bytes are kept in total, split
+/// evenly between excerpts. Excerpts are cut to whole lines unless a single
+/// line fills the whole window. `Stub` keeps nothing and returns no segments.
+/// A file within `max_bytes` is read whole and excerpted by lines alone.
+pub fn read(
+    entry: &FileEntry,
+    strategy: OversizedStrategy,
+    max_lines: usize,
+    max_bytes: u64,
+) -> Result<Vec<Segment>> {
+    let size = match &entry.source {
+        EntrySource::Memory(bytes) => bytes.len() as u64,
+        EntrySource::Disk | EntrySource::Blob { .. } => entry.size,
+    };
+    if strategy == OversizedStrategy::Stub || size == 0 {
+        return Ok(Vec::new());
+    }
+    let mut reader = WindowReader::open(entry)?;
+    if max_bytes >= size {
+        let bytes = reader.read(&(0..size))?;
+        return Ok(by_lines(&bytes, strategy, max_lines));
+    }
+
+    let windows = windows(strategy, size, max_bytes);
+    let lines_per_window = (max_lines / windows.len()).max(1);
+    let last = windows.len() - 1;
+
+    let mut segments = Vec::new();
+    let mut position = 0;
+    for (index, window) in windows.into_iter().enumerate() {
+        let bytes = reader.read(&window)?;
+        // The last window shows how the file ends
+        let from_end = index == last && index > 0;
+        let kept = trim(
+            &bytes,
+            window.start == 0,
+            window.end == size,
+            lines_per_window,
+            from_end,
+        );
+
+        let start = window.start + kept.start as u64;
+        if start > position {
+            push_omitted(&mut segments, start - position);
+        }
+        if !kept.is_empty() {
+            segments.push(Segment::Kept(bytes[kept.clone()].to_vec()));
+        }
+        position = start + kept.len() as u64;
+    }
+    if size > position {
+        push_omitted(&mut segments, size - position);
+    }
+    Ok(segments)
+}
+
+/// Picks whole lines of `bytes` the way `windows` picks byte ranges, with
+/// `max_lines` as the budget
+fn by_lines(bytes: &[u8], strategy: OversizedStrategy, max_lines: usize) -> Vec<Segment> {
+    let starts: Vec<usize> = std::iter::once(0)
+        .chain(
+            bytes
+                .iter()
+                .enumerate()
+                .filter(|(_, &b)| b == b'\n')
+                .map(|(i, _)| i + 1),
+        )
+        .filter(|&start| start < bytes.len())
+        .collect();
+    let offset = |line: u64| starts.get(line as usize).copied().unwrap_or(bytes.len());
+
+    let mut segments = Vec::new();
+    let mut position = 0;
+    for window in windows(strategy, starts.len() as u64, max_lines as u64) {
+        let (start, end) = (offset(window.start), offset(window.end));
+        if start > position {
+            push_omitted(&mut segments, (start - position) as u64);
+        }
+        if end > start {
+            segments.push(Segment::Kept(bytes[start..end].to_vec()));
+        }
+        position = end;
+    }
+    if bytes.len() > position {
+        push_omitted(&mut segments, (bytes.len() - position) as u64);
+    }
+    segments
+}
+
+/// Reads windows of a file without loading the rest of it
+enum WindowReader<'a> {
+    Memory(Cow<'a, [u8]>),
+    Disk(File),
+}
+
+/// Line breaks in bytes `range` of a file, e.g. to number the lines of
+/// excerpts after a part that was left out
+pub fn count_lines(entry: &FileEntry, range: Range<u64>) -> Result<usize> {
+    let mut reader = WindowReader::open(entry)?;
+    let mut count = 0;
+    let mut start = range.start;
+    while start < range.end {
+        let end = (start + COUNT_CHUNK).min(range.end);
+        count += reader
+            .read(&(start..end))?
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count();
+        start = end;
+    }
+    Ok(count)
+}
+
+impl WindowReader<'_> {
+    fn open(entry: &FileEntry) -> Result<WindowReader<'_>> {
+        Ok(match &entry.source {
+            EntrySource::Memory(bytes) => WindowReader::Memory(Cow::Borrowed(bytes)),
+            EntrySource::Blob { repo, oid } => {
+                WindowReader::Memory(Cow::Owned(crate::tree::read_blob(repo, *oid)?))
+            }
+            EntrySource::Disk => WindowReader::Disk(File::open(&entry.absolute_path)?),
+        })
+    }
+
+    fn read(&mut self, window: &Range<u64>) -> Result<Vec<u8>> {
+        match self {
+            WindowReader::Memory(bytes) => {
+                Ok(bytes[window.start as usize..window.end as usize].to_vec())
+            }
+            WindowReader::Disk(file) => {
+                file.seek(SeekFrom::Start(window.start))?;
+                let mut bytes = Vec::new();
+                file.take(window.end - window.start)
+                    .read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+fn push_omitted(segments: &mut Vec<Segment>, bytes: u64) {
+    if let Some(Segment::Omitted(previous)) = segments.last_mut() {
+        *previous += bytes;
+    } else {
+        segments.push(Segment::Omitted(bytes));
+    }
+}
+
+/// Ranges `strategy` picks from `size` bytes or lines, `budget` of them in
+/// total and in file order
+fn windows(strategy: OversizedStrategy, size: u64, budget: u64) -> Vec<Range<u64>> {
+    if strategy == OversizedStrategy::Stub || size == 0 {
+        return Vec::new();
+    }
+    if budget >= size {
+        return std::iter::once(0..size).collect();
+    }
+    match strategy {
+        OversizedStrategy::Stub => Vec::new(),
+        OversizedStrategy::Head => std::iter::once(0..budget).collect(),
+        OversizedStrategy::HeadTail => {
+            let tail = budget / 2;
+            vec![0..budget - tail, size - tail..size]
+        }
+        OversizedStrategy::Sample => {
+            // Fewer windows when the budget can't give each of them a unit
+            let count = SAMPLE_WINDOWS.min(budget);
+            if count < 2 {
+                return std::iter::once(0..budget).collect();
+            }
+            let width = budget / count;
+            (0..count)
+                .map(|i| {
+                    let start = (size - width) * i / (count - 1);
+                    start..start + width
+                })
+                .collect()
+        }
+    }
+}
+
+/// The part of a window made of whole lines, at most `max_lines` of them,
+/// taken from the front or, with `from_end`, from the back
+///
+/// A window that starts mid-file starts mid-line, so its first partial line
+/// is dropped; likewise the last partial line of one that ends mid-file.
+fn trim(
+    window: &[u8],
+    at_start: bool,
+    at_end: bool,
+    max_lines: usize,
+    from_end: bool,
+) -> Range<usize> {
+    let mut start = 0;
+    let mut end = window.len();
+    if !at_start {
+        match window.iter().position(|&b| b == b'\n') {
+            Some(newline) => start = newline + 1,
+            None => return 0..window.len(),
+        }
+    }
+    if !at_end {
+        match window[start..].iter().rposition(|&b| b == b'\n') {
+            Some(newline) => end = start + newline + 1,
+            None => return 0..window.len(),
+        }
+    }
+
+    let lines = &window[start..end];
+    if from_end {
+        // Ignore the final line break so it doesn't count as an empty line
+        let body = lines.strip_suffix(b"\n").unwrap_or(lines);
+        if let Some(newline) = body
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &b)| b == b'\n')
+            .nth(max_lines - 1)
+            .map(|(i, _)| i)
+        {
+            start += newline + 1;
+        }
+    } else if let Some(newline) = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| b == b'\n')
+        .nth(max_lines - 1)
+        .map(|(i, _)| i)
+    {
+        end = start + newline + 1;
+    }
+    start..end
+}
+
+/// Decodes kept bytes as UTF-8, tolerating a character cut in half at either
+/// edge of a window; `None` when they aren't text
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    // Continuation bytes of a character that started before the window
+    let skip = bytes
+        .iter()
+        .take(3)
+        .take_while(|&&b| b & 0xC0 == 0x80)
+        .count();
+    let bytes = &bytes[skip..];
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text.to_string()),
+        // An incomplete character at the very end
+        Err(e) if e.error_len().is_none() => {
+            Some(String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned())
+        }
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf, sync::Arc};
+    use tempfile::TempDir;
+
+    fn numbered(lines: usize) -> String {
+        (1..=lines).map(|i| format!("line {:04}\n", i)).collect()
+    }
+
+    fn memory_entry(content: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from("big.log"),
+            size: content.len() as u64,
+            is_oversized: true,
+            source: EntrySource::Memory(Arc::from(content.as_bytes())),
+            ..Default::default()
+        }
+    }
+
+    fn render(segments: &[Segment]) -> Vec<String> {
+        segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Kept(bytes) => String::from_utf8(bytes.clone()).unwrap(),
+                Segment::Omitted(bytes) => format!("<{}>", bytes),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_head_and_tail() {
+        // 1000 lines of 10 bytes each
+        let entry = memory_entry(&numbered(1000));
+
+        let head = read(&entry, OversizedStrategy::Head, 3, 1024).unwrap();
+        assert_eq!(
+            render(&head),
+            vec!["line 0001\nline 0002\nline 0003\n", "<9970>"]
+        );
+
+        let head_tail = read(&entry, OversizedStrategy::HeadTail, 4, 1024).unwrap();
+        assert_eq!(
+            render(&head_tail),
+            vec!["line 0001\nline 0002\n", "<9960>", "line 0999\nline 1000\n"]
+        );
+
+        // The byte budget binds before the line limit: 25 bytes hold two lines
+        let head = read(&entry, OversizedStrategy::Head, 100, 25).unwrap();
+        assert_eq!(render(&head), vec!["line 0001\nline 0002\n", "<9980>"]);
+
+        assert!(read(&entry, OversizedStrategy::Stub, 100, 1024)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sample_from_disk() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("big.log");
+        let content = numbered(1000);
+        fs::write(&path, &content).unwrap();
+        let entry = FileEntry {
+            path: PathBuf::from("big.log"),
+            absolute_path: path,
+            size: content.len() as u64,
+            is_oversized: true,
+            ..Default::default()
+        };
+
+        let segments = read(&entry, OversizedStrategy::Sample, 5, 500).unwrap();
+        let kept: Vec<String> = render(&segments)
+            .into_iter()
+            .filter(|s| !s.starts_with('<'))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                "line 0001\n",
+                "line 0249\n",
+                "line 0497\n",
+                "line 0744\n",
+                "line 1000\n"
+            ]
+        );
+        // Kept and omitted bytes add up to the whole file
+        let total: u64 = segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Kept(bytes) => bytes.len() as u64,
+                Segment::Omitted(bytes) => *bytes,
+            })
+            .sum();
+        assert_eq!(total, content.len() as u64);
+
+        assert_eq!(count_lines(&entry, 0..total).unwrap(), 1000);
+        assert_eq!(count_lines(&entry, 10..35).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_long_line_and_small_file() {
+        // A single line longer than the budget is cut mid-line
+        let entry = memory_entry(&"x".repeat(100));
+        let head = read(&entry, OversizedStrategy::HeadTail, 10, 20).unwrap();
+        assert_eq!(
+            render(&head),
+            vec!["x".repeat(10), "<80>".into(), "x".repeat(10)]
+        );
+
+        // A byte budget covering the whole file leaves the line limit
+        let entry = memory_entry("a\nb\nc\nd\ne");
+        let sample = read(&entry, OversizedStrategy::Sample, 2, 1024).unwrap();
+        assert_eq!(render(&sample), vec!["a\n", "<6>", "e"]);
+        let head_tail = read(&entry, OversizedStrategy::HeadTail, 3, 1024).unwrap();
+        assert_eq!(render(&head_tail), vec!["a\nb\n", "<4>", "e"]);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("héllo".as_bytes()).as_deref(), Some("héllo"));
+        // "é" cut at both edges
+        assert_eq!(decode(b"\xA9llo h\xC3").as_deref(), Some("llo h"));
+        assert_eq!(decode(b"\xFF\xFEbinary"), None);
+    }
+}
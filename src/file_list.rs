@@ -0,0 +1,81 @@
+use crate::error::Result;
+use std::io::Read;
+use std::path::PathBuf;
+use tracing::info;
+
+/// Reads the paths for `--files-from` from a file, or from stdin for `-`
+pub fn read(from: &str) -> Result<Vec<PathBuf>> {
+    let mut bytes = Vec::new();
+    if from == "-" {
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        bytes = std::fs::read(from)?;
+    }
+    let paths = parse(&bytes);
+    let name = if from == "-" { "stdin" } else { from };
+    info!("Read {} path(s) from {}", paths.len(), name);
+    Ok(paths)
+}
+
+/// Splits a list on NUL bytes when it has any (as from `find -print0` or
+/// `rg -l0`), otherwise on newlines; blank entries are dropped
+fn parse(bytes: &[u8]) -> Vec<PathBuf> {
+    let entries: Vec<&[u8]> = if bytes.contains(&0) {
+        bytes.split(|&b| b == 0).collect()
+    } else {
+        bytes
+            .split(|&b| b == b'\n')
+            .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+            .collect()
+    };
+    entries
+        .into_iter()
+        .filter(|entry| !entry.iter().all(u8::is_ascii_whitespace))
+        .map(path_from_bytes)
+        .collect()
+}
+
+/// Keeps names that aren't valid UTF-8 as they are, so they still name the file
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+/// Paths aren't bytes on other platforms, so names must be UTF-8
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(b"src/main.rs\r\nsrc/lib.rs\n\nREADME.md"),
+            vec![
+                PathBuf::from("src/main.rs"),
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("README.md")
+            ]
+        );
+        // With NUL separators, newlines are part of the name
+        assert_eq!(
+            parse(b"odd\nname.txt\0src/lib.rs\0"),
+            vec![PathBuf::from("odd\nname.txt"), PathBuf::from("src/lib.rs")]
+        );
+        assert!(parse(b"\n\n").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let paths = parse(b"caf\xe9.txt\0src/lib.rs\0");
+        assert_eq!(paths[0].as_os_str().as_bytes(), b"caf\xe9.txt");
+        assert_eq!(paths[1], PathBuf::from("src/lib.rs"));
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{
+    config::FilterConfig,
+    error::{NomnomError, Result},
+};
+use regex::{Match, Regex, RegexSet, SetMatches};
+use std::{collections::HashMap, ops::Range, path::Path, sync::LazyLock};
+use tracing::debug;
+
+/// Named character sets for `entropy` filters, with their default minimum
+/// entropy in bits per character
+const CHARSETS: &[(&str, &str, f64)] = &[
+    ("base64", "A-Za-z0-9+/=", 4.5),
+    ("base64url", "A-Za-z0-9_=-", 4.5),
+    ("alphanumeric", "A-Za-z0-9", 4.5),
+    ("hex", "0-9a-fA-F", 3.0),
+];
+
+const DEFAULT_CHARSET: &str = "base64";
+
+/// Default minimum entropy for a custom character class
+const DEFAULT_MIN_ENTROPY: f64 = 4.5;
+
+/// Long enough for a string of distinct characters to reach 4.5 bits
+/// (log2 24 ≈ 4.58); shorter candidates could never reach the default
+const DEFAULT_MIN_LENGTH: usize = 24;
+
+/// Lockfiles, whose package hashes an `entropy` filter leaves alone
+const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "go.sum",
+    "Gemfile.lock",
+    "composer.lock",
+    "flake.lock",
+    "packages.lock.json",
+    "Package.resolved",
+    "pubspec.lock",
+    "mix.lock",
+    "gradle.lockfile",
+];
+
+/// Algorithm prefixes of hashes in lockfiles: `sha512-` (npm, yarn, pnpm),
+/// `sha256:` (poetry, uv), `h1:` (go.sum) and `10c0/` (yarn berry)
+const HASH_PREFIX: &str = r"(?:(?:sha(?:1|224|256|384|512)|md5)[-:=]|h1:|\d+c\d+/)";
+
+static BEFORE_HASH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!("{}$", HASH_PREFIX)).expect("valid regex"));
+
+static PREFIXED_HASH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!("^{}", HASH_PREFIX)).expect("valid regex"));
+
+/// What a filter does to matching content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    Redact,
+    Truncate,
+    /// Redacts strings that look random enough to be secrets
+    Entropy,
+    /// Unrecognised `type`; reported per file and otherwise ignored
+    Unknown,
+}
+
+impl FilterKind {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "redact" => FilterKind::Redact,
+            "truncate" => FilterKind::Truncate,
+            "entropy" => FilterKind::Entropy,
+            _ => FilterKind::Unknown,
+        }
+    }
+}
+
+/// A configured filter with its patterns compiled
+#[derive(Debug, Clone)]
+pub struct CompiledFilter {
+    pub config: FilterConfig,
+    pub kind: FilterKind,
+    /// The content pattern; for `entropy` filters, the candidate strings
+    pub regex: Regex,
+    pub file_regex: Option<Regex>,
+    /// Entropy from which an `entropy` filter's candidates are secrets
+    pub min_entropy: f64,
+}
+
+impl CompiledFilter {
+    /// Whether the filter applies to `path`: always, unless its `file_pattern` fails to match
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.file_regex
+            .as_ref()
+            .is_none_or(|file_regex| file_regex.is_match(path))
+    }
+
+    /// Finds what the filter replaces in the content of `path`
+    ///
+    /// For `entropy` filters these are the candidates at or above the minimum
+    /// entropy, except for package hashes in lockfiles.
+    pub fn find_matches<'h>(&self, text: &'h str, path: &str) -> Vec<Match<'h>> {
+        let matches = self.regex.find_iter(text);
+        if self.kind != FilterKind::Entropy {
+            return matches.collect();
+        }
+        let lockfile = is_lockfile(path);
+        matches
+            .filter(|m| shannon_entropy(m.as_str()) >= self.min_entropy)
+            .filter(|m| !(lockfile && is_lockfile_hash(text, m.range())))
+            .collect()
+    }
+}
+
+/// Shannon entropy of `s` in bits per character
+pub fn shannon_entropy(s: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in s.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let total = counts.values().sum::<usize>() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn is_lockfile(path: &str) -> bool {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| LOCKFILES.contains(&name))
+}
+
+/// Whether `range` of a lockfile holds a package hash: hex digits, or a
+/// digest after an algorithm prefix
+fn is_lockfile_hash(text: &str, range: Range<usize>) -> bool {
+    let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let token = &text[range.clone()];
+    // Whether the charset takes in the prefix's separator decides which side
+    // of the candidate it ends up on
+    BEFORE_HASH.is_match(&text[line_start..range.start])
+        || PREFIXED_HASH.is_match(token)
+        || token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The candidate strings of an `entropy` filter, and the entropy from which
+/// they are redacted
+pub fn entropy_candidates(filter: &FilterConfig) -> (String, f64) {
+    let charset = filter.charset.as_deref().unwrap_or(DEFAULT_CHARSET);
+    let (class, default_entropy) = CHARSETS
+        .iter()
+        .find(|(name, _, _)| *name == charset)
+        .map_or((charset, DEFAULT_MIN_ENTROPY), |(_, class, entropy)| {
+            (*class, *entropy)
+        });
+    let min_length = filter.min_length.unwrap_or(DEFAULT_MIN_LENGTH);
+    (
+        format!("[{}]{{{},}}", class, min_length),
+        filter.min_entropy.unwrap_or(default_entropy),
+    )
+}
+
+/// Filters compiled once at startup
+///
+/// Every content pattern is also compiled into a single `RegexSet`, so one
+/// pass over a file tells which filters can match at all; the rest are
+/// skipped without running their individual regexes.
+#[derive(Debug, Clone)]
+pub struct FilterEngine {
+    filters: Vec<CompiledFilter>,
+    prefilter: Option<RegexSet>,
+}
+
+impl FilterEngine {
+    /// Compiles the filters, failing on the first invalid pattern
+    pub fn new(filters: &[FilterConfig]) -> Result<Self> {
+        let invalid = |index: usize, field: &'static str, value: &str, reason: String| {
+            NomnomError::InvalidFilter {
+                index,
+                field,
+                value: value.to_string(),
+                reason,
+            }
+        };
+
+        let mut compiled = Vec::with_capacity(filters.len());
+        for (index, filter) in filters.iter().enumerate() {
+            let kind = FilterKind::from_name(&filter.r#type);
+            let (regex, min_entropy) = match kind {
+                FilterKind::Entropy => {
+                    if filter.min_length == Some(0) {
+                        let reason = "must be at least 1".to_string();
+                        return Err(invalid(index, "min_length", "0", reason));
+                    }
+                    let (candidates, min_entropy) = entropy_candidates(filter);
+                    // Also rejects NaN, which no entropy compares against
+                    if !(min_entropy > 0.0 && min_entropy.is_finite()) {
+                        let value = min_entropy.to_string();
+                        let reason = "must be a positive number of bits".to_string();
+                        return Err(invalid(index, "min_entropy", &value, reason));
+                    }
+                    let charset = filter.charset.as_deref().unwrap_or(DEFAULT_CHARSET);
+                    let regex = Regex::new(&candidates)
+                        .map_err(|e| invalid(index, "charset", charset, e.to_string()))?;
+                    (regex, min_entropy)
+                }
+                FilterKind::Redact | FilterKind::Truncate if filter.pattern.is_empty() => {
+                    let reason = format!("{} filters need a pattern", filter.r#type);
+                    return Err(invalid(index, "pattern", "", reason));
+                }
+                _ => (
+                    Regex::new(&filter.pattern)
+                        .map_err(|e| invalid(index, "pattern", &filter.pattern, e.to_string()))?,
+                    0.0,
+                ),
+            };
+            let file_regex = match &filter.file_pattern {
+                Some(file_pattern) => Some(
+                    Regex::new(file_pattern)
+                        .map_err(|e| invalid(index, "file_pattern", file_pattern, e.to_string()))?,
+                ),
+                None => None,
+            };
+            compiled.push(CompiledFilter {
+                config: filter.clone(),
+                kind,
+                regex,
+                file_regex,
+                min_entropy,
+            });
+        }
+
+        // Every pattern compiled alone, so the set can only fail on size limits
+        let prefilter = match RegexSet::new(compiled.iter().map(|f| f.regex.as_str())) {
+            Ok(set) => Some(set),
+            Err(e) => {
+                debug!("Filter prefilter disabled: {}", e);
+                None
+            }
+        };
+
+        Ok(Self {
+            filters: compiled,
+            prefilter,
+        })
+    }
+
+    pub fn filters(&self) -> &[CompiledFilter] {
+        &self.filters
+    }
+
+    /// Finds the filters whose content pattern can match somewhere in `text`
+    pub fn candidates(&self, text: &str) -> Candidates {
+        Candidates(self.prefilter.as_ref().map(|set| set.matches(text)))
+    }
+}
+
+/// Result of the prefilter pass over a file's original content
+pub struct Candidates(Option<SetMatches>);
+
+impl Candidates {
+    /// Whether filter `index` may match; always true without a prefilter
+    pub fn may_match(&self, index: usize) -> bool {
+        self.0.as_ref().is_none_or(|set| set.matched(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(r#type: &str, pattern: &str, file_pattern: Option<&str>) -> FilterConfig {
+        FilterConfig {
+            r#type: r#type.to_string(),
+            pattern: pattern.to_string(),
+            file_pattern: file_pattern.map(str::to_string),
+            threshold: None,
+            charset: None,
+            min_length: None,
+            min_entropy: None,
+        }
+    }
+
+    #[test]
+    fn test_compiles_default_filters() -> Result<()> {
+        let config = crate::config::Config::default();
+        let engine = FilterEngine::new(&config.filters)?;
+        assert_eq!(engine.filters().len(), config.filters.len());
+        assert!(engine.prefilter.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_pattern_names_filter_index() {
+        let filters = vec![
+            filter("redact", "password", None),
+            filter("truncate", "<svg.*?</svg>", Some("(unclosed")),
+        ];
+
+        match FilterEngine::new(&filters) {
+            Err(NomnomError::InvalidFilter { index, field, .. }) => {
+                assert_eq!(index, 1);
+                assert_eq!(field, "file_pattern");
+            }
+            other => panic!("expected InvalidFilter, got {:?}", other),
+        }
+
+        let err = FilterEngine::new(&[filter("redact", "[a-", None)]).unwrap_err();
+        assert!(err.to_string().contains("filters[0].pattern"));
+    }
+
+    #[test]
+    fn test_candidates() -> Result<()> {
+        let engine = FilterEngine::new(&[
+            filter("redact", "password", None),
+            filter("redact", r"sk-[a-z0-9]{8}", None),
+            filter("truncate", "<svg", Some(r"\.html$")),
+        ])?;
+
+        let candidates = engine.candidates("my password is sk-abcd1234");
+        assert!(candidates.may_match(0));
+        assert!(candidates.may_match(1));
+        assert!(!candidates.may_match(2));
+
+        assert!(engine.filters()[2].applies_to("index.html"));
+        assert!(!engine.filters()[2].applies_to("main.rs"));
+        assert!(engine.filters()[0].applies_to("main.rs"));
+        Ok(())
+    }
+
+    fn entropy(charset: Option<&str>) -> FilterConfig {
+        FilterConfig {
+            charset: charset.map(str::to_string),
+            ..filter("entropy", "", None)
+        }
+    }
+
+    #[test]
+    fn test_entropy_filter() -> Result<()> {
+        assert_eq!(shannon_entropy("AAAAAAAA"), 0.0);
+        assert_eq!(shannon_entropy("abcd"), 2.0);
+
+        let engine = FilterEngine::new(&[entropy(None), entropy(Some("hex"))])?;
+        let [base64, hex] = engine.filters() else {
+            panic!("expected two filters");
+        };
+        let found = |filter: &CompiledFilter, text: &str, path: &str| -> Vec<String> {
+            filter
+                .find_matches(text, path)
+                .iter()
+                .map(|m| m.as_str().to_string())
+                .collect()
+        };
+
+        let text = "let key = \"tK9xQ2mZ7vLpR4wB8nYc3HjD6fGs1aEu0iOqWkXz\";\n\
+                    let name = ThisIsJustAnOrdinaryIdentifierName;\n\
+                    use src/components/settings/index;\n";
+        assert_eq!(
+            found(base64, text, "src/main.rs"),
+            vec!["tK9xQ2mZ7vLpR4wB8nYc3HjD6fGs1aEu0iOqWkXz"]
+        );
+
+        // Package hashes in lockfiles are left alone, secrets in them are not
+        let integrity = "sha512-XRcglhh3p2lHAu4gFg75i5owZ3/uttIZh11iKj+W2fqc4Iu8OvwIHkDSftaw4gURo0WA2fT2oguwTa4HChLwJw==";
+        let lockfile = format!(
+            "\"integrity\": \"{}\",\n\"resolved\": \"https://registry.example/a?auth=tK9xQ2mZ7vLpR4wB8nYc3HjD6fGs1aEu0iOqWkXz\"\n",
+            integrity
+        );
+        assert_eq!(
+            found(base64, &lockfile, "web/package-lock.json"),
+            vec!["auth=tK9xQ2mZ7vLpR4wB8nYc3HjD6fGs1aEu0iOqWkXz"]
+        );
+        assert_eq!(found(base64, &lockfile, "web/config.json").len(), 2);
+
+        let checksum =
+            "checksum = \"8b917c4b6163bc82ef4aff025c6f5f4d54205232c4595f39b7b43008256a6cb7\"\n";
+        assert!(found(hex, checksum, "Cargo.lock").is_empty());
+        assert_eq!(found(hex, checksum, "checksums.txt").len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_entropy_filter_options() {
+        let custom = FilterConfig {
+            min_length: Some(8),
+            min_entropy: Some(2.5),
+            ..entropy(Some("a-z"))
+        };
+        assert_eq!(entropy_candidates(&custom), ("[a-z]{8,}".to_string(), 2.5));
+        assert_eq!(
+            entropy_candidates(&entropy(Some("hex"))),
+            ("[0-9a-fA-F]{24,}".to_string(), 3.0)
+        );
+
+        let err = FilterEngine::new(&[entropy(Some("z-a"))]).unwrap_err();
+        assert!(err.to_string().contains("filters[0].charset 'z-a'"));
+
+        let err = FilterEngine::new(&[filter("redact", "", None)]).unwrap_err();
+        assert!(matches!(
+            err,
+            NomnomError::InvalidFilter {
+                index: 0,
+                field: "pattern",
+                ..
+            }
+        ));
+
+        // Thresholds that would redact everything or nothing
+        for (min_length, min_entropy, field) in [
+            (Some(0), None, "min_length"),
+            (None, Some(-1.0), "min_entropy"),
+            (None, Some(f64::NAN), "min_entropy"),
+            (None, Some(0.0), "min_entropy"),
+        ] {
+            let config = FilterConfig {
+                min_length,
+                min_entropy,
+                ..entropy(None)
+            };
+            match FilterEngine::new(&[config]) {
+                Err(NomnomError::InvalidFilter { field: name, .. }) => assert_eq!(name, field),
+                other => panic!("expected InvalidFilter, got {:?}", other),
+            }
+        }
+    }
+}
@@ -1,5 +1,12 @@
+use crate::auth::{redact_url, Authenticator};
+use crate::cache::{self, CloneCache};
 use crate::error::{NomnomError, Result};
-use git2::{build::RepoBuilder, FetchOptions, Progress, RemoteCallbacks, Repository};
+use crate::submodule;
+use git2::{
+    build::CheckoutBuilder, Direction, ErrorCode, FetchOptions, Oid, Progress, Remote,
+    RemoteCallbacks, Repository,
+};
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 use tracing::{debug, info};
 
@@ -66,8 +73,16 @@ pub fn parse_git_source(source: &str) -> GitSource {
             url = source[..hash_pos].to_string();
         }
 
-        // Then handle reference syntax: url@ref
-        if let Some(at_pos) = url.rfind('@') {
+        // Then handle reference syntax: url@ref, looking past any user@ in the authority
+        let path_start = url
+            .find("://")
+            .and_then(|scheme_end| {
+                url[scheme_end + 3..]
+                    .find('/')
+                    .map(|slash| scheme_end + 3 + slash)
+            })
+            .unwrap_or(0);
+        if let Some(at_pos) = url[path_start..].rfind('@').map(|pos| path_start + pos) {
             reference = Some(url[at_pos + 1..].to_string());
             url = url[..at_pos].to_string();
         }
@@ -80,20 +95,57 @@ pub fn parse_git_source(source: &str) -> GitSource {
     }
 }
 
-/// Clones a remote git repository into a temporary directory with shallow clone optimization
+/// How much of a remote repository to fetch, and from where
+#[derive(Debug, Clone)]
+pub struct CloneOptions {
+    /// Number of commits to fetch, or `None` for full history
+    pub depth: Option<u32>,
+    /// Fetch into a persistent mirror instead of straight into the checkout
+    pub cache: Option<CloneCache>,
+    /// Resolve the reference from the cache without touching the network
+    pub offline: bool,
+    /// SSH key files to try before the usual `~/.ssh` ones
+    pub ssh_keys: Vec<PathBuf>,
+    /// Also fetch and check out submodules, recursively
+    pub submodules: bool,
+    /// Also fetch every branch and tag, so revisions other than the reference
+    /// resolve too (the sides of a diff range, for instance)
+    pub all_refs: bool,
+}
+
+impl Default for CloneOptions {
+    fn default() -> Self {
+        Self {
+            depth: Some(1),
+            cache: None,
+            offline: false,
+            ssh_keys: Vec::new(),
+            submodules: false,
+            all_refs: false,
+        }
+    }
+}
+
+/// Clones a remote git repository into a temporary directory
 ///
 /// Returns a tuple of (TempDir, actual_path) where:
-/// - TempDir acts as a guard for automatic cleanup  
+/// - TempDir acts as a guard for automatic cleanup
 /// - actual_path points to the subpath within the cloned repo if specified
 ///
 /// Features:
-/// - Shallow clone (depth=1) by default for bandwidth efficiency
-/// - Support for specific git references (branches, tags, commits)
-/// - Automatic subpath validation
-pub fn clone_repo(source: &str) -> Result<(TempDir, std::path::PathBuf)> {
+/// - Fetches only the requested reference (or the default branch), shallow to
+///   `options.depth` commits
+/// - Support for specific git references (branches, tags, full commit SHAs)
+/// - Sparse checkout of just the requested subpath
+/// - With `options.cache`, fetches into a persistent bare mirror and checks out
+///   from it; with `options.offline` as well, never touches the network
+/// - With `options.submodules`, recursively checks out the submodules under
+///   the subpath
+/// - With `options.all_refs`, also fetches every branch and tag
+pub fn clone_repo(source: &str, options: &CloneOptions) -> Result<(TempDir, std::path::PathBuf)> {
     let git_source = parse_git_source(source);
 
-    info!("Cloning repository: {}", git_source.url);
+    info!("Cloning repository: {}", redact_url(&git_source.url));
     if let Some(ref reference) = git_source.reference {
         info!("Target reference: {}", reference);
     }
@@ -109,8 +161,91 @@ pub fn clone_repo(source: &str) -> Result<(TempDir, std::path::PathBuf)> {
 
     debug!("Created temporary directory: {:?}", temp_dir.path());
 
-    // Set up progress callback for large repositories
+    let repo = Repository::init(temp_dir.path())?;
+    // Also the base that relative submodule URLs resolve against
+    repo.remote("origin", &git_source.url)?;
+    let target = fetch_into(
+        &repo,
+        &git_source.url,
+        git_source.reference.as_deref(),
+        options,
+    )?;
+
+    info!("Successfully fetched repository to: {:?}", temp_dir.path());
+
+    checkout_commit(&repo, target, git_source.subpath.as_deref())?;
+    if options.submodules {
+        submodule::update(&repo, git_source.subpath.as_deref(), options)?;
+    }
+
+    // Determine the actual processing path
+    let processing_path = if let Some(subpath) = git_source.subpath {
+        let full_subpath = temp_dir.path().join(&subpath);
+        info!("Using subpath: {:?}", full_subpath);
+        full_subpath
+    } else {
+        temp_dir.path().to_path_buf()
+    };
+
+    Ok((temp_dir, processing_path))
+}
+
+/// Fetches `reference` (or the default branch) from the repository's `origin`
+/// remote at `url`, through the clone cache when one is configured, and returns
+/// the commit it points to
+pub(crate) fn fetch_into(
+    repo: &Repository,
+    url: &str,
+    reference: Option<&str>,
+    options: &CloneOptions,
+) -> Result<Oid> {
+    let auth = Authenticator::from_env(url, &options.ssh_keys);
+    fetch_with_auth(repo, url, reference, options, &auth)
+}
+
+/// Like `fetch_into`, with the credentials offered to the server
+pub(crate) fn fetch_with_auth(
+    repo: &Repository,
+    url: &str,
+    reference: Option<&str>,
+    options: &CloneOptions,
+    auth: &Authenticator,
+) -> Result<Oid> {
+    match options.cache {
+        Some(ref cache) => {
+            let mirror = cache.open_mirror(url, options.offline)?;
+            let target = if options.offline {
+                info!("Offline: using cached mirror {:?}", mirror.path());
+                cache::resolve_offline(&mirror, reference)?
+            } else {
+                let mut remote = mirror.find_remote("origin")?;
+                fetch_reference(&mirror, &mut remote, reference, options, auth)
+                    .map_err(|e| auth.explain(url, e))?
+            };
+            cache::share_mirror(&mirror, repo)?;
+            Ok(target)
+        }
+        None => {
+            let mut remote = repo.find_remote("origin")?;
+            fetch_reference(repo, &mut remote, reference, options, auth)
+                .map_err(|e| auth.explain(url, e))
+        }
+    }
+}
+
+fn remote_callbacks(auth: &Authenticator) -> RemoteCallbacks<'_> {
     let mut remote_callbacks = RemoteCallbacks::new();
+    remote_callbacks.credentials(auth.credentials());
+    remote_callbacks
+}
+
+fn fetch_options<'a>(
+    repo: &Repository,
+    options: &CloneOptions,
+    auth: &'a Authenticator,
+) -> FetchOptions<'a> {
+    // Set up progress callback for large repositories
+    let mut remote_callbacks = remote_callbacks(auth);
     remote_callbacks.transfer_progress(|stats: Progress| {
         if stats.received_objects() == stats.total_objects() {
             debug!(
@@ -130,121 +265,174 @@ pub fn clone_repo(source: &str) -> Result<(TempDir, std::path::PathBuf)> {
         true
     });
 
-    // Configure fetch options for shallow clone
     let mut fetch_options = FetchOptions::new();
     fetch_options.remote_callbacks(remote_callbacks);
-
-    // Use shallow clone (depth=1) by default for efficiency
-    debug!("Using shallow clone (depth=1) for bandwidth efficiency");
-
-    // Clone with RepoBuilder for advanced options
-    let mut builder = RepoBuilder::new();
-    builder.fetch_options(fetch_options);
-
-    // If a specific reference is requested, try to clone that branch directly
-    if let Some(ref reference) = git_source.reference {
-        debug!("Attempting to clone specific reference: {}", reference);
-        builder.branch(reference);
-    }
-
-    let repo = builder
-        .clone(&git_source.url, temp_dir.path())
-        .map_err(NomnomError::Git)?;
-
-    info!("Successfully cloned repository to: {:?}", temp_dir.path());
-
-    // If we couldn't clone the specific reference directly, try to checkout after clone
-    if let Some(ref reference) = git_source.reference {
-        if let Err(e) = checkout_reference(&repo, reference) {
-            debug!(
-                "Could not checkout reference '{}' after clone: {}",
-                reference, e
-            );
-            // Continue anyway - the user might have specified a commit SHA that's not a branch
+    match options.depth {
+        Some(depth) => {
+            debug!("Using shallow fetch (depth={})", depth);
+            fetch_options.depth(depth.min(i32::MAX as u32) as i32);
         }
+        None if repo.is_shallow() => {
+            // A cached mirror may hold an earlier shallow fetch; deepen it fully
+            debug!("Unshallowing repository");
+            fetch_options.depth(i32::MAX);
+        }
+        None => debug!("Fetching full history"),
     }
+    fetch_options
+}
 
-    // Determine the actual processing path
-    let processing_path = if let Some(subpath) = git_source.subpath {
-        let full_subpath = temp_dir.path().join(&subpath);
+/// Fetches the requested reference (or the remote's default branch) and
+/// returns the commit it points to
+fn fetch_reference(
+    repo: &Repository,
+    remote: &mut Remote,
+    reference: Option<&str>,
+    options: &CloneOptions,
+    auth: &Authenticator,
+) -> Result<Oid> {
+    // List the remote's refs to find out what the reference names
+    let (advertised, default_branch) = {
+        let connection =
+            remote.connect_auth(Direction::Fetch, Some(remote_callbacks(auth)), None)?;
+        let advertised: Vec<String> = connection
+            .list()?
+            .iter()
+            .map(|head| head.name().to_string())
+            .collect();
+        let default_branch = connection
+            .default_branch()
+            .ok()
+            .and_then(|b| b.as_str().map(str::to_string));
+        (advertised, default_branch)
+    };
 
-        // Verify the subpath exists
-        if !full_subpath.exists() {
-            return Err(crate::error::NomnomError::Io(std::io::Error::new(
+    let remote_ref = match reference {
+        None => Some(default_branch.ok_or_else(|| {
+            NomnomError::Io(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
-                format!("Subpath '{}' not found in repository", subpath),
-            )));
+                "Remote has no default branch",
+            ))
+        })?),
+        Some(reference) => {
+            let candidates = if reference.starts_with("refs/") {
+                vec![reference.to_string()]
+            } else {
+                vec![
+                    format!("refs/heads/{}", reference),
+                    format!("refs/tags/{}", reference),
+                ]
+            };
+            candidates
+                .into_iter()
+                .find(|candidate| advertised.contains(candidate))
         }
-
-        info!("Using subpath: {:?}", full_subpath);
-        full_subpath
-    } else {
-        temp_dir.path().to_path_buf()
     };
 
-    Ok((temp_dir, processing_path))
-}
-
-/// Attempts to checkout a specific reference (branch, tag, or commit)
-fn checkout_reference(repo: &Repository, reference: &str) -> Result<()> {
-    use git2::{ObjectType, Oid};
-
-    debug!("Attempting to checkout reference: {}", reference);
-
-    // Try to resolve the reference
-    let obj = if let Ok(oid) = Oid::from_str(reference) {
-        // It's a commit SHA
-        repo.find_object(oid, Some(ObjectType::Commit))
-            .map_err(NomnomError::Git)?
-    } else {
-        // Try as a branch or tag reference
-        let refname = if reference.starts_with("refs/") {
-            reference.to_string()
-        } else {
-            // Try common reference patterns
-            for prefix in &["refs/heads/", "refs/tags/", "refs/remotes/origin/"] {
-                let full_ref = format!("{}{}", prefix, reference);
-                if repo.find_reference(&full_ref).is_ok() {
-                    let resolved = repo
-                        .find_reference(&full_ref)
-                        .and_then(|r| r.resolve())
-                        .map_err(NomnomError::Git)?;
-                    return checkout_reference_object(repo, &resolved.target().unwrap());
+    let mut fetch_options = fetch_options(repo, options, auth);
+    match (remote_ref, reference) {
+        (Some(remote_ref), _) => {
+            let local_ref = match remote_ref.strip_prefix("refs/heads/") {
+                Some(branch) => format!("refs/remotes/origin/{}", branch),
+                None => remote_ref.clone(),
+            };
+            debug!("Fetching {} into {}", remote_ref, local_ref);
+            remote.fetch(
+                &[format!("+{}:{}", remote_ref, local_ref)],
+                Some(&mut fetch_options),
+                None,
+            )?;
+            if reference.is_none() {
+                // Remember the default branch so offline runs can find it
+                repo.reference_symbolic("refs/remotes/origin/HEAD", &local_ref, true, "")?;
+            }
+            if options.all_refs {
+                fetch_branches_and_tags(remote, &mut fetch_options)?;
+            }
+            Ok(repo.find_reference(&local_ref)?.peel_to_commit()?.id())
+        }
+        (None, Some(reference)) if Oid::from_str(reference).is_ok() && reference.len() == 40 => {
+            // Servers only hand out commits by full SHA
+            debug!("Fetching commit {}", reference);
+            match remote.fetch(&[reference], Some(&mut fetch_options), None) {
+                Ok(()) => {
+                    if options.all_refs {
+                        fetch_branches_and_tags(remote, &mut fetch_options)?;
+                    }
+                    return Ok(repo.find_commit(Oid::from_str(reference)?)?.id());
+                }
+                Err(e) if options.depth.is_some() || e.code() == ErrorCode::Auth => {
+                    return Err(e.into())
                 }
+                // Some servers refuse commits no ref points at; find it in the full history
+                Err(e) => debug!("Fetching {} by id failed: {}", reference, e),
             }
-            return Err(NomnomError::Io(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Reference '{}' not found", reference),
-            )));
-        };
-
-        let resolved_ref = repo
-            .find_reference(&refname)
-            .and_then(|r| r.resolve())
-            .map_err(NomnomError::Git)?;
-        repo.find_object(resolved_ref.target().unwrap(), Some(ObjectType::Commit))
-            .map_err(NomnomError::Git)?
-    };
+            fetch_all(repo, remote, reference, options, auth)
+        }
+        (None, Some(reference)) if options.depth.is_none() => {
+            // Abbreviated SHAs and other revisions need the full history to resolve
+            fetch_all(repo, remote, reference, options, auth)
+        }
+        (None, reference) => Err(NomnomError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "Reference '{}' not found (use a full commit SHA, or --full-history for other revisions)",
+                reference.unwrap_or_default()
+            ),
+        ))),
+    }
+}
 
-    checkout_reference_object(repo, &obj.id())
+/// Fetches every branch and tag, then resolves `reference` among them
+fn fetch_all(
+    repo: &Repository,
+    remote: &mut Remote,
+    reference: &str,
+    options: &CloneOptions,
+    auth: &Authenticator,
+) -> Result<Oid> {
+    debug!("Fetching all branches and tags to resolve {}", reference);
+    let mut fetch_options = fetch_options(repo, options, auth);
+    fetch_branches_and_tags(remote, &mut fetch_options)?;
+    Ok(repo.revparse_single(reference)?.peel_to_commit()?.id())
 }
 
-/// Performs the actual checkout of a git object
-fn checkout_reference_object(repo: &Repository, oid: &git2::Oid) -> Result<()> {
-    use git2::{build::CheckoutBuilder, ObjectType};
+/// Fetches every branch into `refs/remotes/origin` and every tag
+fn fetch_branches_and_tags(remote: &mut Remote, fetch_options: &mut FetchOptions) -> Result<()> {
+    remote.fetch(
+        &[
+            "+refs/heads/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*",
+        ],
+        Some(fetch_options),
+        None,
+    )?;
+    Ok(())
+}
 
-    let obj = repo
-        .find_object(*oid, Some(ObjectType::Commit))
-        .map_err(NomnomError::Git)?;
+/// Checks out a commit, limited to `subpath` when one is given
+pub(crate) fn checkout_commit(repo: &Repository, oid: Oid, subpath: Option<&str>) -> Result<()> {
+    let commit = repo.find_commit(oid)?;
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
 
-    // Checkout the commit
-    repo.checkout_tree(&obj, Some(CheckoutBuilder::default().force()))
-        .map_err(NomnomError::Git)?;
+    if let Some(subpath) = subpath {
+        let subpath = subpath.trim_matches('/');
+        // Verify the subpath exists
+        if commit.tree()?.get_path(Path::new(subpath)).is_err() {
+            return Err(NomnomError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Subpath '{}' not found in repository", subpath),
+            )));
+        }
+        debug!("Sparse checkout of: {}", subpath);
+        checkout.path(subpath);
+    }
 
-    // Update HEAD to point to the new commit
-    repo.set_head_detached(*oid).map_err(NomnomError::Git)?;
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))?;
+    repo.set_head_detached(oid)?;
 
-    debug!("Successfully checked out reference: {}", oid);
+    debug!("Successfully checked out commit: {}", oid);
     Ok(())
 }
 
@@ -264,5 +452,28 @@ pub fn is_remote_source(source: &str) -> bool {
         || url.ends_with(".git") // Keep original case for .git extension
 }
 
-// Note: Unit tests for git functionality are located in tests/git_reference_test.rs
-// Integration tests that require network access are in tests/git_remote_ingestion_test.rs
+/// Whether a git URL names a repository on the local filesystem: a `file://`
+/// URL or a path, as opposed to a URL with a scheme or an scp-like
+/// `host:path`
+pub fn is_local_url(url: &str) -> bool {
+    if url
+        .get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file://"))
+    {
+        return true;
+    }
+    if url.contains("://") {
+        return false;
+    }
+    // As in git, a colon before any slash makes it scp-like, unless it follows
+    // a Windows drive letter
+    match url.find(':') {
+        Some(1) if url.starts_with(|c: char| c.is_ascii_alphabetic()) => true,
+        Some(colon) => url[..colon].contains('/'),
+        None => true,
+    }
+}
+
+// Note: Unit tests for git functionality are located in tests/git_reference_test.rs,
+// clone tests against local bare repositories in tests/git_clone_test.rs, and
+// integration tests that require network access in tests/git_remote_ingestion_test.rs
@@ -0,0 +1,343 @@
+use crate::{diff::ChangeStatus, error::Result, walker::FileEntry};
+use git2::{BlameOptions, Commit, DiffOptions, Oid, Repository, Sort};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+/// Number of contributors listed per file in a blame summary
+const TOP_CONTRIBUTORS: usize = 3;
+
+/// One commit in the git log section
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSummary {
+    /// Abbreviated hash
+    pub id: String,
+    pub author: String,
+    /// Author date as `YYYY-MM-DD`
+    pub date: String,
+    pub subject: String,
+}
+
+/// Who wrote how many of a file's lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contributor {
+    pub name: String,
+    pub lines: usize,
+}
+
+/// A file's last-modified commit and top contributors by surviving lines
+#[derive(Debug, Clone)]
+pub struct BlameSummary {
+    pub last_commit: CommitSummary,
+    pub contributors: Vec<Contributor>,
+    pub total_lines: usize,
+}
+
+/// Git context added to the output, shared by every writer
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    /// Recent commits touching the selected files, newest first
+    pub commits: Vec<CommitSummary>,
+    /// Blame summaries keyed by output path
+    pub blame: HashMap<String, BlameSummary>,
+}
+
+/// Collects the last `git_log` commits touching `files` and, with `blame`, a
+/// blame summary per file
+///
+/// `source` is any path inside the repository and `reference` the revision the
+/// files were read at (`HEAD` for the working copy). Files outside the
+/// repository or not committed at `reference` are skipped.
+pub fn collect(
+    source: &Path,
+    reference: &str,
+    files: &[FileEntry],
+    git_log: Option<usize>,
+    blame: bool,
+) -> Result<History> {
+    let repo = Repository::discover(source)?;
+    let Some(workdir) = repo.workdir() else {
+        return Ok(History::default());
+    };
+    let workdir = workdir.canonicalize()?;
+    let head = repo.revparse_single(reference)?.peel_to_commit()?;
+
+    // Map each selected file to its path in the repository
+    let paths: Vec<(&FileEntry, PathBuf)> = files
+        .iter()
+        .filter_map(|entry| Some((entry, repo_path(&workdir, &entry.absolute_path)?)))
+        .collect();
+
+    let mut history = History::default();
+    if let Some(limit) = git_log {
+        let selected: HashSet<&Path> = paths.iter().map(|(_, path)| path.as_path()).collect();
+        history.commits = recent_commits(&repo, &head, &selected, limit)?;
+        info!("Git log: {} commit(s)", history.commits.len());
+    }
+    if blame {
+        info!("Computing blame for {} file(s)", paths.len());
+        let mut commits = HashMap::new();
+        for (entry, path) in &paths {
+            let deleted = entry
+                .change
+                .as_ref()
+                .is_some_and(|c| c.status == ChangeStatus::Deleted);
+            if entry.is_binary || deleted {
+                continue;
+            }
+            match blame_file(&repo, head.id(), path, &mut commits) {
+                Ok(Some(summary)) => {
+                    let output = entry.path.to_string_lossy().replace('\\', "/");
+                    history.blame.insert(output, summary);
+                }
+                Ok(None) => {}
+                Err(e) => debug!("No blame for {:?}: {}", path, e),
+            }
+        }
+    }
+    Ok(history)
+}
+
+fn repo_path(workdir: &Path, absolute_path: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = absolute_path.strip_prefix(workdir) {
+        return Some(relative.to_path_buf());
+    }
+    let canonical = absolute_path.canonicalize().ok()?;
+    canonical.strip_prefix(workdir).ok().map(Path::to_path_buf)
+}
+
+/// Walks history from `head`, keeping commits whose changes against their
+/// first parent touch one of `selected`
+///
+/// Diffs are limited to the selected paths, so each commit only compares
+/// the files that matter.
+fn recent_commits(
+    repo: &Repository,
+    head: &Commit,
+    selected: &HashSet<&Path>,
+    limit: usize,
+) -> Result<Vec<CommitSummary>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+    revwalk.push(head.id())?;
+
+    let mut opts = DiffOptions::new();
+    opts.skip_binary_check(true).disable_pathspec_match(true);
+    for path in selected {
+        opts.pathspec(path);
+    }
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        if commits.len() >= limit {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff =
+            repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut opts))?;
+        let touches = diff.deltas().any(|delta| {
+            [delta.new_file().path(), delta.old_file().path()]
+                .into_iter()
+                .flatten()
+                .any(|path| selected.contains(path))
+        });
+        if touches {
+            commits.push(summarize(&commit));
+        }
+    }
+    Ok(commits)
+}
+
+fn blame_file(
+    repo: &Repository,
+    newest: Oid,
+    path: &Path,
+    commits: &mut HashMap<Oid, (i64, CommitSummary)>,
+) -> Result<Option<BlameSummary>> {
+    let mut opts = BlameOptions::new();
+    opts.newest_commit(newest);
+    let blame = repo.blame_file(path, Some(&mut opts))?;
+
+    let mut lines_by_author: HashMap<String, usize> = HashMap::new();
+    let mut last: Option<(i64, CommitSummary)> = None;
+    let mut total_lines = 0;
+    for hunk in blame.iter() {
+        let lines = hunk.lines_in_hunk();
+        total_lines += lines;
+        let name = hunk
+            .final_signature()
+            .name()
+            .unwrap_or("unknown")
+            .to_string();
+        *lines_by_author.entry(name).or_default() += lines;
+
+        let oid = hunk.final_commit_id();
+        if let Entry::Vacant(vacant) = commits.entry(oid) {
+            let commit = repo.find_commit(oid)?;
+            vacant.insert((commit.time().seconds(), summarize(&commit)));
+        }
+        let (time, summary) = &commits[&oid];
+        if last.as_ref().is_none_or(|(last_time, _)| time > last_time) {
+            last = Some((*time, summary.clone()));
+        }
+    }
+
+    let Some((_, last_commit)) = last else {
+        return Ok(None);
+    };
+    let mut contributors: Vec<Contributor> = lines_by_author
+        .into_iter()
+        .map(|(name, lines)| Contributor { name, lines })
+        .collect();
+    contributors.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.name.cmp(&b.name)));
+    contributors.truncate(TOP_CONTRIBUTORS);
+
+    Ok(Some(BlameSummary {
+        last_commit,
+        contributors,
+        total_lines,
+    }))
+}
+
+fn summarize(commit: &Commit) -> CommitSummary {
+    let author = commit.author();
+    let time = author.when();
+    CommitSummary {
+        id: commit.id().to_string()[..7].to_string(),
+        author: author.name().unwrap_or("unknown").to_string(),
+        date: format_date(time.seconds() + i64::from(time.offset_minutes()) * 60),
+        subject: commit.summary().unwrap_or("").to_string(),
+    }
+}
+
+/// Formats seconds since the Unix epoch as a `YYYY-MM-DD` civil date
+fn format_date(seconds: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm
+    let days = seconds.div_euclid(86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, walker::Walker};
+    use git2::{Signature, Time};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_as(repo: &Repository, author: &str, day: i64, message: &str) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new(
+            author,
+            "dev@example.com",
+            &Time::new(1_700_000_000 + day * 86_400, 0),
+        )
+        .unwrap();
+        let parents: Vec<Commit> = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<&Commit> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    fn create_repo() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("src/lib.rs"), "a\nb\nc\n").unwrap();
+        commit_as(&repo, "Alice", 0, "Add lib");
+        fs::write(root.join("docs/guide.md"), "guide\n").unwrap();
+        commit_as(&repo, "Carol", 1, "Write docs");
+        fs::write(root.join("src/lib.rs"), "a\nb\nc\nd\n").unwrap();
+        commit_as(&repo, "Bob", 2, "Extend lib\n\nWith a body");
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(-86_400), "1969-12-31");
+    }
+
+    #[test]
+    fn test_git_log_touching_selected_files() {
+        let (dir, _repo) = create_repo();
+        let files = Walker::new(Config::default())
+            .walk(dir.path().join("src"))
+            .unwrap();
+
+        let history = collect(dir.path(), "HEAD", &files, Some(10), false).unwrap();
+        let subjects: Vec<&str> = history.commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["Extend lib", "Add lib"]);
+        assert_eq!(history.commits[0].author, "Bob");
+        assert_eq!(history.commits[0].date, "2023-11-16");
+        assert_eq!(history.commits[0].id.len(), 7);
+        assert!(history.blame.is_empty());
+
+        let history = collect(dir.path(), "HEAD", &files, Some(1), false).unwrap();
+        assert_eq!(history.commits.len(), 1);
+    }
+
+    #[test]
+    fn test_blame_summary() {
+        let (dir, _repo) = create_repo();
+        let files = Walker::new(Config::default())
+            .walk(dir.path().join("src"))
+            .unwrap();
+
+        let history = collect(dir.path(), "HEAD", &files, None, true).unwrap();
+        assert!(history.commits.is_empty());
+        let blame = history.blame.values().next().unwrap();
+        assert_eq!(history.blame.len(), 1);
+        assert_eq!(blame.last_commit.subject, "Extend lib");
+        assert_eq!(blame.total_lines, 4);
+        assert_eq!(
+            blame.contributors,
+            vec![
+                Contributor {
+                    name: "Alice".to_string(),
+                    lines: 3
+                },
+                Contributor {
+                    name: "Bob".to_string(),
+                    lines: 1
+                }
+            ]
+        );
+    }
+}
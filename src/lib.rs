@@ -1,7 +1,27 @@
+pub mod archive;
+pub mod attributes;
+pub mod auth;
+pub mod budget;
+pub mod cache;
 pub mod cli;
 pub mod config;
+pub mod diff;
 pub mod error;
+pub mod excerpt;
+pub mod file_list;
+pub mod filter;
 pub mod git;
+pub mod history;
+pub mod lines;
+pub mod outline;
 pub mod output;
+pub mod pipeline;
 pub mod processor;
+pub mod sources;
+pub mod split;
+pub mod status;
+pub mod strip;
+pub mod submodule;
+pub mod tokenizer;
+pub mod tree;
 pub mod walker;
@@ -0,0 +1,210 @@
+use serde_json::{json, Value};
+use std::ops::Range;
+
+/// A replacement of a byte range of text
+pub type Edit = (Range<usize>, String);
+
+/// Text after a transformation, with the line of the input each of its lines
+/// starts in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edited {
+    pub text: String,
+    /// Zero-based input line index of each output line
+    pub origins: Vec<usize>,
+}
+
+/// Applies non-overlapping `edits`, given in file order, to `text`
+///
+/// A line a replacement adds is traced to the replaced line at the same
+/// offset, or to the last replaced line when the replacement is longer.
+pub fn apply_edits(text: &str, edits: &[Edit]) -> Edited {
+    let mut edited = Edited {
+        text: String::with_capacity(text.len()),
+        origins: vec![0],
+    };
+    let mut line = 0;
+    let mut position = 0;
+    for (range, replacement) in edits {
+        edited.keep(&text[position..range.start], &mut line);
+        let at_line_start = edited.text.is_empty() || edited.text.ends_with('\n');
+        let first = line;
+        line += text[range.clone()].matches('\n').count();
+        if at_line_start && replacement.is_empty() {
+            // The line now starts with what follows the removed text
+            *edited.origins.last_mut().expect("origins start non-empty") = line;
+        }
+        let added = replacement.matches('\n').count();
+        edited
+            .origins
+            .extend((1..=added).map(|j| (first + j).min(line)));
+        edited.text.push_str(replacement);
+        position = range.end;
+    }
+    edited.keep(&text[position..], &mut line);
+    edited.origins.truncate(line_count(&edited.text));
+    edited
+}
+
+impl Edited {
+    /// `text` with no edits
+    pub fn unchanged(text: String) -> Self {
+        let origins = (0..line_count(&text)).collect();
+        Self { text, origins }
+    }
+
+    /// Follows this transformation with `next`, which was applied to its text
+    pub fn then(self, next: Edited) -> Self {
+        Self {
+            text: next.text,
+            // Text added after the last line belongs to it
+            origins: next
+                .origins
+                .iter()
+                .map(|&i| {
+                    self.origins
+                        .get(i)
+                        .or(self.origins.last())
+                        .copied()
+                        .unwrap_or(0)
+                })
+                .collect(),
+        }
+    }
+
+    /// The edited text, moving `numbers` along with it when line numbers are
+    /// being tracked
+    pub fn into_text(self, numbers: Option<&mut LineNumbers>) -> String {
+        if let Some(numbers) = numbers {
+            numbers.follow(&self.origins);
+        }
+        self.text
+    }
+
+    /// Copies unchanged input, whose first line is input line `line`
+    fn keep(&mut self, slice: &str, line: &mut usize) {
+        for _ in slice.matches('\n') {
+            *line += 1;
+            self.origins.push(*line);
+        }
+        self.text.push_str(slice);
+    }
+}
+
+/// Lines in `text`, not counting an empty one after a final line break
+pub fn line_count(text: &str) -> usize {
+    text.split_inclusive('\n').count()
+}
+
+/// The original line number of each line of text that has been through
+/// filters and transformations, so output can point back into the file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineNumbers(Vec<Option<usize>>);
+
+impl LineNumbers {
+    /// Numbers the lines of `text` from `first`
+    pub fn new(text: &str, first: usize) -> Self {
+        Self((first..first + line_count(text)).map(Some).collect())
+    }
+
+    /// Leaves the lines of `text` unnumbered, e.g. for markers that aren't
+    /// from the file
+    pub fn unnumbered(text: &str) -> Self {
+        Self(vec![None; line_count(text)])
+    }
+
+    /// Renumbers after a transformation, given the input line each output
+    /// line starts in
+    pub fn follow(&mut self, origins: &[usize]) {
+        self.0 = origins
+            .iter()
+            .map(|&origin| self.0.get(origin).copied().flatten())
+            .collect();
+    }
+
+    /// Adds the numbers of text appended to `text`; when `text` doesn't end
+    /// with a line break, the first appended line continues its last line
+    pub fn append(&mut self, text: &str, other: LineNumbers) {
+        let continued = !text.is_empty() && !text.ends_with('\n');
+        self.0
+            .extend(other.0.into_iter().skip(usize::from(continued)));
+    }
+
+    /// The numbers of lines `range`
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        Self(self.0[range.start.min(self.0.len())..range.end.min(self.0.len())].to_vec())
+    }
+
+    /// Prefixes each line of `text` with its right-aligned number, leaving
+    /// the number blank for lines that aren't from the file
+    pub fn render(&self, text: &str) -> String {
+        let width = self
+            .0
+            .iter()
+            .flatten()
+            .max()
+            .map_or(1, |n| n.to_string().len());
+        let mut result = String::with_capacity(text.len() + (width + 3) * self.0.len());
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            match self.0.get(i).copied().flatten() {
+                Some(number) => result.push_str(&format!("{:>width$} | ", number)),
+                None => result.push_str(&format!("{:width$} | ", "")),
+            }
+            result.push_str(line);
+        }
+        result
+    }
+
+    /// The numbers as a JSON array, with `null` for unnumbered lines
+    pub fn to_json(&self) -> Value {
+        json!(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(range: Range<usize>, replacement: &str) -> Edit {
+        (range, replacement.to_string())
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let text = "a\nb {\n  c\n  d\n}\ne\n";
+        // Collapse the block from `{` to `}`
+        let edited = apply_edits(text, &[edit(4..15, "{ … }")]);
+        assert_eq!(edited.text, "a\nb { … }\ne\n");
+        assert_eq!(edited.origins, vec![0, 1, 5]);
+
+        // A line added by a replacement belongs to the replaced line
+        let edited = apply_edits(text, &[edit(2..5, "x\ny")]);
+        assert_eq!(edited.text, "a\nx\ny\n  c\n  d\n}\ne\n");
+        assert_eq!(edited.origins, vec![0, 1, 1, 2, 3, 4, 5]);
+
+        // Dropping whole lines
+        let edited = apply_edits("a\nb\nc\nd", &[edit(0..2, ""), edit(4..6, "")]);
+        assert_eq!(edited.text, "b\nd");
+        assert_eq!(edited.origins, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let mut numbers = LineNumbers::new("a\nb\nc\n", 10);
+        numbers.follow(&[0, 2]);
+        assert_eq!(numbers, LineNumbers(vec![Some(10), Some(12)]));
+
+        let text = "a\nc";
+        numbers.append(text, LineNumbers::new("c continued\nd\n", 12));
+        numbers.append("\n", LineNumbers::unnumbered("[gap]\n"));
+        assert_eq!(
+            numbers,
+            LineNumbers(vec![Some(10), Some(12), Some(13), None])
+        );
+
+        assert_eq!(
+            numbers.render("a\nc\nd\n[gap]\n"),
+            "10 | a\n12 | c\n13 | d\n   | [gap]\n"
+        );
+        assert_eq!(numbers.slice(1..3).to_json(), json!([12, 13]));
+    }
+}
@@ -1,9 +1,29 @@
+mod archive;
+mod attributes;
+mod auth;
+mod budget;
+mod cache;
 mod cli;
 mod config;
+mod diff;
 mod error;
+mod excerpt;
+mod file_list;
+mod filter;
 mod git;
+mod history;
+mod lines;
+mod outline;
 mod output;
+mod pipeline;
 mod processor;
+mod sources;
+mod split;
+mod status;
+mod strip;
+mod submodule;
+mod tokenizer;
+mod tree;
 mod walker;
 
 use cli::Cli;
@@ -11,10 +31,13 @@ use config::Config;
 use error::Result;
 use output::get_writer;
 use processor::Processor;
+use tokenizer::Tokenizer;
 use walker::Walker;
 
 use clap::Parser;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 
@@ -22,11 +45,6 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 const GIT_SHA: &str = env!("VERGEN_GIT_SHA");
 const BUILD_TIMESTAMP: &str = env!("VERGEN_BUILD_TIMESTAMP");
 
-/// Normalize path to use forward slashes regardless of platform
-fn normalize_path_separators<P: AsRef<Path>>(path: P) -> String {
-    path.as_ref().to_string_lossy().replace('\\', "/")
-}
-
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
@@ -36,6 +54,11 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
+    // Handle subcommands before logging setup
+    if let Some(cli::Command::Cache { ref action }) = cli.command {
+        return run_cache_command(action);
+    }
+
     // Handle --validate-config before logging setup
     if cli.validate_config {
         return validate_configuration(cli);
@@ -83,11 +106,6 @@ fn init_logging(quiet: bool, _output_to_stdout: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn tokens_len(chars: usize) -> usize {
-    // ceil(chars / 4 * 1.3)
-    (chars * 13).div_ceil(40)
-}
-
 fn print_default_config() {
     let default_config = Config::default();
     match serde_yaml::to_string(&default_config) {
@@ -119,9 +137,79 @@ fn validate_cli_arguments(cli: &Cli) -> Result<()> {
         config::parse_size(max_size)?;
     }
 
+    // Validate split arguments if provided
+    if let Some(ref split_size) = cli.split_size {
+        config::parse_size(split_size)?;
+    }
+    if (cli.split_tokens.is_some() || cli.split_size.is_some()) && cli.out == "-" {
+        return Err(error::NomnomError::Output(
+            "Splitting output requires an output file (--out)".to_string(),
+        ));
+    }
+
+    // History is read from one repository
+    if cli.source.len() > 1 && (cli.git_log.is_some() || cli.blame_summary) {
+        return Err(error::NomnomError::Source(
+            "--git-log and --blame-summary take a single source".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn run_cache_command(action: &cli::CacheAction) -> anyhow::Result<()> {
+    let cache = cache::CloneCache::default_location()?;
+    match action {
+        cli::CacheAction::List => {
+            let entries = cache.list()?;
+            if entries.is_empty() {
+                println!("No cached repositories in {}", cache.dir().display());
+            }
+            for entry in &entries {
+                println!(
+                    "{:>10}  {:<14}  {}",
+                    format_bytes(entry.size),
+                    format_last_used(entry.last_used),
+                    auth::redact_url(&entry.url)
+                );
+            }
+        }
+        cli::CacheAction::Prune { older_than } => {
+            let max_age = older_than.map(|days| Duration::from_secs(days * 24 * 60 * 60));
+            let removed = cache.prune(max_age)?;
+            for entry in &removed {
+                println!(
+                    "Removed {} ({})",
+                    auth::redact_url(&entry.url),
+                    format_bytes(entry.size)
+                );
+            }
+            let freed: u64 = removed.iter().map(|entry| entry.size).sum();
+            println!(
+                "Pruned {} cached repositories, freed {}",
+                removed.len(),
+                format_bytes(freed)
+            );
+        }
+    }
     Ok(())
 }
 
+fn format_bytes(bytes: u64) -> String {
+    bytesize::ByteSize(bytes).to_string_as(true)
+}
+
+fn format_last_used(last_used: Option<SystemTime>) -> String {
+    let Some(age) = last_used.and_then(|time| SystemTime::now().duration_since(time).ok()) else {
+        return "never used".to_string();
+    };
+    match age.as_secs() / (24 * 60 * 60) {
+        0 => "today".to_string(),
+        1 => "1 day ago".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
 fn validate_configuration(cli: Cli) -> anyhow::Result<()> {
     println!("🔍 NOMNOM Configuration Validation");
     println!("═══════════════════════════════════");
@@ -168,6 +256,16 @@ fn print_config_validation(validation: &config::ConfigValidation, _cli: &Cli) {
     }
     println!();
 
+    // Print ignore files that apply to the source
+    println!("🙈 Ignore Files:");
+    if validation.ignore_files.is_empty() {
+        println!("   (none found)");
+    }
+    for path in &validation.ignore_files {
+        println!("   ✅ {}", path.display());
+    }
+    println!();
+
     // Print validation errors
     if !validation.validation_errors.is_empty() {
         println!("❌ Validation Errors:");
@@ -201,11 +299,46 @@ fn print_config_validation(validation: &config::ConfigValidation, _cli: &Cli) {
         validation.config.max_size,
         validation.config.resolve_max_size().unwrap_or(0)
     );
+    println!(
+        "   oversized_strategy: {} (up to {} lines, {})",
+        validation.config.oversized_strategy.as_str(),
+        validation.config.oversized_lines,
+        validation.config.oversized_bytes
+    );
 
+    println!("   outline: {}", validation.config.outline);
+    println!("   line_numbers: {}", validation.config.line_numbers);
+    if !validation.config.strip.is_empty() {
+        let languages: Vec<&str> = validation.config.strip.keys().map(String::as_str).collect();
+        println!("   strip: {}", languages.join(", "));
+    }
     println!("   format: {}", validation.config.format);
     println!("   ignore_git: {}", validation.config.ignore_git);
+    println!("   tokenizer: {}", validation.config.tokenizer);
+    if !validation.config.include.is_empty() {
+        println!("   include: {}", validation.config.include.join(", "));
+    }
+    if !validation.config.exclude.is_empty() {
+        println!("   exclude: {}", validation.config.exclude.join(", "));
+    }
 
     println!("   filters: {} configured", validation.config.filters.len());
+    let attributes = &validation.config.gitattributes;
+    println!(
+        "   gitattributes: export_ignore={}, linguist_generated={}, linguist_vendored={}",
+        attributes.export_ignore.as_str(),
+        attributes.linguist_generated.as_str(),
+        attributes.linguist_vendored.as_str()
+    );
+    let archives = &validation.config.archives;
+    println!(
+        "   archives: expand_nested={}, max_unpacked_size={}, max_entries={}, max_ratio={}",
+        archives.expand_nested,
+        archives.max_unpacked_size,
+        archives.max_entries,
+        archives.max_ratio
+    );
+    println!("   git_cache: {}", validation.config.git_cache);
     for (i, filter) in validation.config.filters.iter().enumerate() {
         let file_info = match &filter.file_pattern {
             Some(pattern) => format!(" (files: {})", pattern),
@@ -215,11 +348,18 @@ fn print_config_validation(validation: &config::ConfigValidation, _cli: &Cli) {
             Some(t) => format!(" (threshold: {})", t),
             None => String::new(),
         };
+        let pattern = match filter.r#type.as_str() {
+            "entropy" => {
+                let (candidates, min_entropy) = crate::filter::entropy_candidates(filter);
+                format!("{} at entropy >= {}", candidates, min_entropy)
+            }
+            _ => filter.pattern.clone(),
+        };
         println!(
             "     [{}] {}: {}{}{}",
             i + 1,
             filter.r#type,
-            filter.pattern,
+            pattern,
             file_info,
             threshold_info
         );
@@ -233,13 +373,24 @@ fn run(cli: Cli) -> Result<()> {
     validate_cli_arguments(&cli)?;
 
     // Load configuration
-    let mut config = Config::load(cli.config)?;
+    let mut config = Config::load(cli.config.clone())?;
 
     // Override config with CLI arguments
     if let Some(max_size) = &cli.max_size {
         config.max_size = max_size.clone();
     }
     config.format = cli.format.as_str().to_string();
+    config.include.extend(cli.include.iter().cloned());
+    config.exclude.extend(cli.exclude.iter().cloned());
+    if let Some(tokenizer) = &cli.tokenizer {
+        config.tokenizer = tokenizer.clone();
+    }
+    if cli.outline {
+        config.outline = true;
+    }
+    if cli.line_numbers {
+        config.line_numbers = true;
+    }
 
     // Override safe logging if unsafe logging flag is provided
     if cli.unsafe_logging {
@@ -254,80 +405,128 @@ fn run(cli: Cli) -> Result<()> {
         config.resolve_threads()?
     };
 
-    // Determine if source is remote or local and prepare processing path
-    let (_temp_dir_guard, processing_path, repo_root) = if git::is_remote_source(&cli.source) {
-        info!("Source appears to be a remote repository. Cloning...");
-        let (temp_dir, path) = git::clone_repo(&cli.source)?;
-        let repo_root = temp_dir.path().to_path_buf();
-        info!("Repository processing path: {:?}", path);
-        (Some(temp_dir), path, Some(repo_root))
+    let diff_spec = match (&cli.diff, &cli.since) {
+        (Some(range), _) => Some(diff::DiffSpec::Range(range.clone())),
+        (None, Some(reference)) => Some(diff::DiffSpec::Since(reference.clone())),
+        (None, None) => None,
+    };
+
+    let selection = if cli.tracked_only {
+        Some(status::GitSelection::Tracked)
+    } else if cli.dirty {
+        Some(status::GitSelection::Dirty)
     } else {
-        (None, std::path::PathBuf::from(&cli.source), None)
+        None
     };
 
-    info!("Processing source: {:?}", processing_path);
     info!("Output format: {}", config.format);
     info!("Output destination: {}", cli.out);
     info!("Thread count: {}", thread_count);
     info!("Max file size: {}", config.resolve_max_size()?);
 
-    // Walk the directory and collect files
+    let tokenizer = Tokenizer::from_name_or_heuristic(&config.tokenizer);
+    info!("Tokenizer: {}", tokenizer.name());
+
+    // Compile filters before walking so invalid patterns fail immediately
+    let processor = Processor::new(config.clone())?;
+
+    // Walk each source; clones stay alive until the output is written
     let walker = Walker::new(config.clone());
-    let files = if let Some(ref repo_root) = repo_root {
-        // For git repositories, make paths relative to repository root
-        if thread_count > 1 {
-            walker.walk_parallel_relative(&processing_path, thread_count, repo_root)?
-        } else {
-            walker.walk_relative(&processing_path, repo_root)?
-        }
+    let mut walked = Vec::with_capacity(cli.source.len());
+    if let Some(ref from) = cli.files_from {
+        // An explicit list replaces the walk; the source can't be given with it
+        let paths = file_list::read(from)?;
+        walked.push(WalkedSource {
+            _temp_dir: None,
+            path: PathBuf::from("."),
+            reference: "HEAD".to_string(),
+            base: None,
+            files: walker.list(&paths)?,
+        });
+    }
+    for source in cli.source.iter().filter(|_| cli.files_from.is_none()) {
+        walked.push(walk_source(
+            source,
+            &cli,
+            &config,
+            diff_spec.as_ref(),
+            selection,
+            &walker,
+            thread_count,
+        )?);
+    }
+
+    // Several sources are listed side by side under named roots
+    let files = if let [single] = walked.as_mut_slice() {
+        std::mem::take(&mut single.files)
     } else {
-        // For local paths, use normal walking
-        if thread_count > 1 {
-            walker.walk_parallel(&processing_path, thread_count)?
-        } else {
-            walker.walk(&processing_path)?
+        let mut files = Vec::new();
+        for (root, source) in sources::root_names(&cli.source).iter().zip(&mut walked) {
+            info!("Listing {:?} under {}/", source.path, root);
+            sources::prefix(root, source.base.as_deref(), &mut source.files);
+            files.append(&mut source.files);
         }
+        files
     };
 
     info!("Found {} files to process", files.len());
 
-    // Process file contents
-    let processor = Processor::new(config.clone());
-    let mut processed_files = Vec::new();
+    let history = if cli.git_log.is_some() || cli.blame_summary {
+        // Validation limits history to a single source
+        let source = &walked[0];
+        Some(Arc::new(history::collect(
+            &source.path,
+            &source.reference,
+            &files,
+            cli.git_log,
+            cli.blame_summary,
+        )?))
+    } else {
+        None
+    };
 
-    for file in &files {
-        debug!("Processing file: {:?}", file.path);
-        match processor.process_file(file) {
-            Ok(processed) => {
-                processed_files.push(processed);
-            }
-            Err(error::NomnomError::FileTooLarge { path, size }) => {
-                debug!("File too large, adding stub: {} ({} bytes)", path, size);
-                processed_files.push(processor::ProcessedFile {
-                    path: path.clone(),
-                    content: processor::FileContent::Oversized(format!(
-                        "[file too large: {} bytes]",
-                        size
-                    )),
-                });
-            }
-            Err(error::NomnomError::BinaryFile { path }) => {
-                debug!("Binary file detected, adding stub: {}", path);
-                processed_files.push(processor::ProcessedFile {
-                    path: path.clone(),
-                    content: processor::FileContent::Binary("[binary skipped]".to_string()),
-                });
-            }
-            Err(e) => {
-                warn!("Failed to process file {:?}: {}", file.path, e);
-                processed_files.push(processor::ProcessedFile {
-                    path: normalize_path_separators(&file.path),
-                    content: processor::FileContent::Error(format!("[error: {}]", e)),
-                });
+    let writer = get_writer(&config.format, history);
+
+    let split_limit = match (cli.split_tokens, &cli.split_size) {
+        (Some(tokens), _) => Some(split::SplitLimit::Tokens(tokens)),
+        (None, Some(size)) => Some(split::SplitLimit::Bytes(config::parse_size(size)? as usize)),
+        (None, None) => None,
+    };
+
+    // Without a budget or split, stream files straight to the destination
+    if cli.max_tokens.is_none() && split_limit.is_none() {
+        let stats = match stream_output(
+            &cli.out,
+            &files,
+            &processor,
+            writer.as_ref(),
+            &tokenizer,
+            thread_count,
+        ) {
+            Ok(stats) => stats,
+            Err(error::NomnomError::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+                // Gracefully handle broken pipe (e.g., when piping to head/tail)
+                std::process::exit(0);
             }
+            Err(e) => return Err(e),
+        };
+
+        info!("Successfully processed {} files", stats.files);
+        info!(
+            "Output contains {}{} tokens ({} characters)",
+            if tokenizer.is_exact() { "" } else { "~" },
+            stats.tokens,
+            stats.bytes
+        );
+        if cli.out != "-" {
+            info!("Output written to: {}", cli.out);
         }
+        return Ok(());
     }
 
+    // Packing and splitting need every processed file up front
+    let mut processed_files = pipeline::process_all(&files, &processor, thread_count)?;
+
     info!("Successfully processed {} files", processed_files.len());
 
     // Display sample of processed files
@@ -341,18 +540,80 @@ fn run(cli: Cli) -> Result<()> {
                 processor::FileContent::Binary(desc) => format!("Binary: {}", desc),
                 processor::FileContent::Oversized(desc) => format!("Oversized: {}", desc),
                 processor::FileContent::Error(desc) => format!("Error: {}", desc),
+                processor::FileContent::Omitted(desc) => format!("Omitted: {}", desc),
             }
         );
     }
 
-    // Generate output
-    let writer = get_writer(&config.format);
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        for pfile in &processed_files {
+            debug!("Tokens: {} -> {}", pfile.path, tokenizer.count_file(pfile));
+        }
+    }
+
+    // Pack files into the token budget if one was requested
+    if let Some(max_tokens) = cli.max_tokens {
+        let sizes: Vec<u64> = files.iter().map(|f| f.size).collect();
+        let (packed, report) = budget::pack(
+            processed_files,
+            &sizes,
+            writer.as_ref(),
+            &tokenizer,
+            max_tokens,
+        )?;
+        processed_files = packed;
+
+        info!(
+            "Token budget: {} of {} tokens used, {} file(s) included in full, {} stubbed",
+            report.used_tokens,
+            report.max_tokens,
+            report.included,
+            report.dropped.len()
+        );
+        for dropped in &report.dropped {
+            info!(
+                "  Dropped {} (~{} tokens): {}",
+                dropped.path, dropped.tokens, dropped.reason
+            );
+        }
+    }
+
+    // Split into numbered parts if requested
+    if let Some(limit) = split_limit {
+        let split = split::split(
+            &processed_files,
+            writer.as_ref(),
+            &tokenizer,
+            limit,
+            &cli.out,
+        )?;
+
+        let out_path = Path::new(&cli.out);
+        for rendered in &split.parts {
+            let part_path = out_path.with_file_name(&rendered.part.file_name);
+            std::fs::write(&part_path, &rendered.content)?;
+            info!(
+                "Part {} of {} written to: {} ({} tokens, {} file(s))",
+                rendered.part.number,
+                rendered.part.total,
+                part_path.display(),
+                tokenizer.count(&rendered.content),
+                rendered.part.entries.len()
+            );
+        }
+        let index_path = out_path.with_file_name(&split.index_file_name);
+        std::fs::write(&index_path, &split.index)?;
+        info!("Index written to: {}", index_path.display());
+        return Ok(());
+    }
+
     let output = writer.write_output(&processed_files)?;
 
-    // Log token count heuristic
-    let token_count = tokens_len(output.len());
+    // Log token count
+    let token_count = tokenizer.count(&output);
     info!(
-        "Output contains ~{} tokens ({} characters)",
+        "Output contains {}{} tokens ({} characters)",
+        if tokenizer.is_exact() { "" } else { "~" },
         token_count,
         output.len()
     );
@@ -375,3 +636,172 @@ fn run(cli: Cli) -> Result<()> {
 
     Ok(())
 }
+
+/// Files walked from one source, with the clone they were read from
+struct WalkedSource {
+    _temp_dir: Option<tempfile::TempDir>,
+    /// Local path the files were walked from
+    path: PathBuf,
+    /// Revision the files were read at, for git history
+    reference: String,
+    /// Path a plain local walk's paths start with; `None` when they are
+    /// relative to a repository root
+    base: Option<PathBuf>,
+    files: Vec<walker::FileEntry>,
+}
+
+/// Clones a remote source if needed and walks it with the shared filters
+fn walk_source(
+    source: &str,
+    cli: &Cli,
+    config: &Config,
+    diff_spec: Option<&diff::DiffSpec>,
+    selection: Option<status::GitSelection>,
+    walker: &Walker,
+    thread_count: usize,
+) -> Result<WalkedSource> {
+    // A local `path@ref#subpath` is read from the object database, not the working copy
+    let tree_source = if git::is_remote_source(source) {
+        None
+    } else {
+        tree::parse_tree_source(source)
+    };
+    if tree_source.is_some() && diff_spec.is_some() {
+        return Err(error::NomnomError::Diff(
+            "--diff and --since take a plain local path, without @ref or #subpath".to_string(),
+        ));
+    }
+    // A local .tar, .tar.gz or .zip is read in memory rather than extracted
+    let is_archive = tree_source.is_none()
+        && !git::is_remote_source(source)
+        && archive::ArchiveKind::of(Path::new(source)).is_some()
+        && Path::new(source).is_file();
+    if is_archive && (diff_spec.is_some() || selection.is_some()) {
+        return Err(error::NomnomError::Archive(
+            "--diff, --since, --tracked-only and --dirty need a git repository, not an archive"
+                .to_string(),
+        ));
+    }
+    if let Some(selection) = selection {
+        if tree_source.is_some() || git::is_remote_source(source) {
+            return Err(error::NomnomError::Status(format!(
+                "{} takes a plain local path, without a remote URL, @ref or #subpath",
+                selection.flag()
+            )));
+        }
+    }
+
+    // Determine if source is remote or local and prepare processing path
+    let (temp_dir, processing_path, repo_root) = if git::is_remote_source(source) {
+        info!("Source appears to be a remote repository. Cloning...");
+        let depth = if cli.full_history {
+            None
+        } else if diff_spec.is_some() {
+            // Diff ranges need the commits on both sides, so fetch everything
+            info!("Diff mode requested, fetching full history");
+            None
+        } else if cli.git_log.is_some() || cli.blame_summary {
+            info!("Git history requested, fetching full history");
+            None
+        } else {
+            Some(cli.depth)
+        };
+        let use_cache = cli.cache || cli.offline || config.git_cache;
+        let clone_options = git::CloneOptions {
+            depth,
+            cache: if use_cache {
+                Some(cache::CloneCache::default_location()?)
+            } else {
+                None
+            },
+            offline: cli.offline,
+            ssh_keys: config.ssh_keys.clone(),
+            submodules: cli.submodules,
+            // The range may name other branches and tags than the one checked out
+            all_refs: diff_spec.is_some(),
+        };
+        let (temp_dir, path) = git::clone_repo(source, &clone_options)?;
+        let repo_root = temp_dir.path().to_path_buf();
+        info!("Repository processing path: {:?}", path);
+        (Some(temp_dir), path, Some(repo_root))
+    } else if let Some(ref tree_source) = tree_source {
+        (None, tree_source.path.clone(), None)
+    } else {
+        (None, PathBuf::from(source), None)
+    };
+
+    info!("Processing source: {:?}", processing_path);
+
+    let mut files = if let Some(spec) = diff_spec {
+        // Only files changed in the requested range, relative to the repository root
+        diff::changed_files(&processing_path, spec, walker)?
+    } else if let Some(ref tree_source) = tree_source {
+        // Files at a ref, read from git objects relative to the repository root
+        tree::tree_files(tree_source, walker)?
+    } else if is_archive {
+        // Files inside the archive, relative to its root
+        archive::entries(&processing_path, walker)?
+    } else if let Some(ref repo_root) = repo_root {
+        // For git repositories, make paths relative to repository root
+        if thread_count > 1 {
+            walker.walk_parallel_relative(&processing_path, thread_count, repo_root)?
+        } else {
+            walker.walk_relative(&processing_path, repo_root)?
+        }
+    } else {
+        // For local paths, use normal walking
+        let mut files = if thread_count > 1 {
+            walker.walk_parallel(&processing_path, thread_count)?
+        } else {
+            walker.walk(&processing_path)?
+        };
+        if let Some(selection) = selection {
+            status::retain(&processing_path, selection, &mut files)?;
+        }
+        files
+    };
+
+    if diff_spec.is_none() && tree_source.is_none() && !is_archive {
+        submodule::mark(&processing_path, &mut files);
+    }
+
+    // A single file is walked as its own path, so keep its name
+    let plain_walk =
+        diff_spec.is_none() && tree_source.is_none() && repo_root.is_none() && !is_archive;
+    let base = plain_walk.then(|| {
+        if processing_path.is_file() {
+            processing_path
+                .parent()
+                .unwrap_or(&processing_path)
+                .to_path_buf()
+        } else {
+            processing_path.clone()
+        }
+    });
+
+    Ok(WalkedSource {
+        _temp_dir: temp_dir,
+        base,
+        path: processing_path,
+        reference: tree_source.map_or_else(|| "HEAD".to_string(), |t| t.reference),
+        files,
+    })
+}
+
+/// Opens the output destination and streams every file into it
+fn stream_output(
+    out: &str,
+    files: &[walker::FileEntry],
+    processor: &Processor,
+    writer: &dyn output::OutputWriter,
+    tokenizer: &Tokenizer,
+    threads: usize,
+) -> Result<pipeline::StreamStats> {
+    if out == "-" {
+        let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+        pipeline::stream(files, processor, writer, tokenizer, threads, &mut stdout)
+    } else {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(out)?);
+        pipeline::stream(files, processor, writer, tokenizer, threads, &mut file)
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{
+    lines::{apply_edits, Edit, Edited},
+    output::code_language,
+};
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// What a collapsed body is replaced with in languages with braced blocks
+const COLLAPSED_BLOCK: &str = "{ … }";
+
+/// What a collapsed Python body is replaced with, after its docstring if any
+const COLLAPSED_SUITE: &str = "...";
+
+/// Languages with a grammar for outlines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+}
+
+impl Language {
+    fn of(path: &Path) -> Option<Self> {
+        match code_language(&path.to_string_lossy()) {
+            "rust" => Some(Language::Rust),
+            "python" => Some(Language::Python),
+            "javascript" => Some(Language::JavaScript),
+            "typescript" if path.extension().is_some_and(|e| e == "tsx") => Some(Language::Tsx),
+            "typescript" => Some(Language::TypeScript),
+            "go" => Some(Language::Go),
+            _ => None,
+        }
+    }
+
+    fn grammar(&self) -> tree_sitter::Language {
+        match self {
+            Language::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Language::Python => tree_sitter_python::LANGUAGE.into(),
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Language::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Language::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Language::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+
+    /// Node kinds whose `body` field holds the code an outline leaves out
+    fn is_function(&self, kind: &str) -> bool {
+        match self {
+            Language::Rust => kind == "function_item",
+            Language::Python => kind == "function_definition",
+            Language::JavaScript | Language::TypeScript | Language::Tsx => matches!(
+                kind,
+                "function_declaration"
+                    | "generator_function_declaration"
+                    | "function_expression"
+                    | "generator_function"
+                    | "arrow_function"
+                    | "method_definition"
+            ),
+            Language::Go => matches!(
+                kind,
+                "function_declaration" | "method_declaration" | "func_literal"
+            ),
+        }
+    }
+
+    /// Whether a body is a block of statements, as opposed to the single
+    /// expression of an arrow function
+    fn is_block(&self, kind: &str) -> bool {
+        match self {
+            Language::Rust | Language::Python | Language::Go => kind == "block",
+            Language::JavaScript | Language::TypeScript | Language::Tsx => {
+                kind == "statement_block"
+            }
+        }
+    }
+}
+
+/// Reduces source code to its declarations, collapsing function and method
+/// bodies to `{ … }` (`...` in Python, after the docstring)
+///
+/// Types, traits, impls, classes, imports and comments are kept as written.
+/// Returns `None` for languages without a grammar, so callers keep the full
+/// text.
+pub fn outline(text: &str, path: &Path) -> Option<Edited> {
+    let language = Language::of(path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language.grammar()).ok()?;
+    let tree = parser.parse(text, None)?;
+
+    let mut collapsed = Vec::new();
+    collect(tree.root_node(), language, text, &mut collapsed);
+
+    Some(apply_edits(text, &collapsed))
+}
+
+/// Collects the bodies to collapse under `node`, in file order
+fn collect(node: Node, language: Language, text: &str, collapsed: &mut Vec<Edit>) {
+    if language.is_function(node.kind()) {
+        if let Some(body) = node
+            .child_by_field_name("body")
+            .filter(|body| language.is_block(body.kind()))
+        {
+            let replacement = match language {
+                Language::Python => collapsed_suite(body, text),
+                _ => COLLAPSED_BLOCK.to_string(),
+            };
+            collapsed.push((body.byte_range(), replacement));
+            return;
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect(child, language, text, collapsed);
+    }
+}
+
+/// A Python body reduced to its docstring, if it starts with one, and `...`
+fn collapsed_suite(body: Node, text: &str) -> String {
+    let docstring = body
+        .named_child(0)
+        .filter(|statement| statement.kind() == "expression_statement")
+        .filter(|statement| {
+            statement
+                .named_child(0)
+                .is_some_and(|expression| expression.kind() == "string")
+        });
+    // The body's indentation, unless it shares a line with the `def`
+    let indent = text[..body.start_byte()]
+        .rsplit('\n')
+        .next()
+        .filter(|prefix| prefix.chars().all(char::is_whitespace));
+    match (docstring, indent) {
+        (Some(docstring), Some(indent)) => format!(
+            "{}\n{}{}",
+            &text[body.start_byte()..docstring.end_byte()],
+            indent,
+            COLLAPSED_SUITE
+        ),
+        _ => COLLAPSED_SUITE.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outlined(path: &str, text: &str) -> Option<String> {
+        outline(text, Path::new(path)).map(|edited| edited.text)
+    }
+
+    #[test]
+    fn test_rust_outline() {
+        let text = r#"use std::fmt;
+
+/// A point
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    /// Creates a point
+    pub fn new(x: i32) -> Self {
+        let x = x.max(0);
+        Self { x }
+    }
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &str {
+        "shape"
+    }
+}
+"#;
+        assert_eq!(
+            outlined("src/point.rs", text).unwrap(),
+            r#"use std::fmt;
+
+/// A point
+pub struct Point {
+    x: i32,
+}
+
+impl Point {
+    /// Creates a point
+    pub fn new(x: i32) -> Self { … }
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &str { … }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_python_outline() {
+        let text = r#"import os
+
+
+class Loader:
+    """Loads things."""
+
+    def load(self, path):
+        """Reads a file."""
+        with open(path) as f:
+            return f.read()
+
+    def close(self): self.f.close()
+
+
+def main():
+    Loader().load(os.environ["FILE"])
+"#;
+        assert_eq!(
+            outlined("loader.py", text).unwrap(),
+            r#"import os
+
+
+class Loader:
+    """Loads things."""
+
+    def load(self, path):
+        """Reads a file."""
+        ...
+
+    def close(self): ...
+
+
+def main():
+    ...
+"#
+        );
+    }
+
+    #[test]
+    fn test_typescript_and_go_outline() {
+        let text = r#"export interface User {
+  name: string;
+}
+
+export class Store {
+  get(id: number): User {
+    return this.users[id];
+  }
+}
+
+export const handler = async (event: Event) => {
+  await process(event);
+};
+const double = (x: number) => x * 2;
+"#;
+        assert_eq!(
+            outlined("src/store.ts", text).unwrap(),
+            r#"export interface User {
+  name: string;
+}
+
+export class Store {
+  get(id: number): User { … }
+}
+
+export const handler = async (event: Event) => { … };
+const double = (x: number) => x * 2;
+"#
+        );
+
+        let text = "package main\n\ntype Server struct {\n\tport int\n}\n\n// Run starts the server\nfunc (s *Server) Run() error {\n\treturn nil\n}\n";
+        assert_eq!(
+            outlined("main.go", text).unwrap(),
+            "package main\n\ntype Server struct {\n\tport int\n}\n\n// Run starts the server\nfunc (s *Server) Run() error { … }\n"
+        );
+    }
+
+    #[test]
+    fn test_unknown_language() {
+        assert_eq!(outlined("notes.txt", "fn main() {}"), None);
+        assert_eq!(outlined("Makefile", "all:\n\tcargo build\n"), None);
+    }
+}
@@ -1,31 +1,117 @@
 use crate::{
+    diff::FileChange,
     error::Result,
+    history::{BlameSummary, CommitSummary, History},
     processor::{FileContent, ProcessedFile},
 };
 use serde_json::{json, Value};
-use std::{collections::HashMap, fmt, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    fmt,
+    io::Write,
+    path::Path,
+    sync::Arc,
+};
 
 /// Normalize path to use forward slashes regardless of platform
 fn normalize_path_separators<P: AsRef<Path>>(path: P) -> String {
     path.as_ref().to_string_lossy().replace('\\', "/")
 }
 
+/// Renders output incrementally into any `std::io::Write`
+///
+/// A document is written as a header (including the directory tree), then one
+/// call to `write_file` per file in output order, then a footer. This lets
+/// callers stream files as they are processed instead of holding them all.
 pub trait OutputWriter {
-    fn write_output(&self, files: &[ProcessedFile]) -> Result<String>;
+    /// Writes everything before the first file; `part` is set for split outputs
+    fn write_header(
+        &self,
+        out: &mut dyn Write,
+        tree: &DirectoryTree,
+        part: Option<&OutputPart>,
+    ) -> Result<()>;
+
+    /// Writes one file; `index` is its zero-based position in the document
+    fn write_file(&self, out: &mut dyn Write, index: usize, file: &ProcessedFile) -> Result<()>;
+
+    /// Writes everything after the last file
+    fn write_footer(&self, out: &mut dyn Write, file_count: usize) -> Result<()>;
+
+    /// Writes the index that maps files to the parts containing them
+    fn write_index(&self, out: &mut dyn Write, parts: &[OutputPart]) -> Result<()>;
+
+    /// Renders a complete document in memory
+    fn write_output(&self, files: &[ProcessedFile]) -> Result<String> {
+        self.render(None, files)
+    }
+
+    /// Renders one part of a split output, headed by its number and file list
+    fn write_part(&self, part: &OutputPart, files: &[ProcessedFile]) -> Result<String> {
+        self.render(Some(part), files)
+    }
+
+    fn render(&self, part: Option<&OutputPart>, files: &[ProcessedFile]) -> Result<String> {
+        let mut out = Vec::new();
+        self.write_header(&mut out, &DirectoryTree::new(files), part)?;
+        for (index, file) in files.iter().enumerate() {
+            self.write_file(&mut out, index, file)?;
+        }
+        self.write_footer(&mut out, files.len())?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
 }
 
+/// One numbered part of a split output
+#[derive(Debug, Clone)]
+pub struct OutputPart {
+    pub number: usize,
+    pub total: usize,
+    /// File name the part is written to
+    pub file_name: String,
+    /// Files in this part; files split across parts carry their line range
+    pub entries: Vec<String>,
+}
+
+/// Shown after a directory that is the root of a git submodule
+const SUBMODULE_MARKER: &str = "(submodule)";
+
 pub struct DirectoryTree {
     entries: Vec<String>,
 }
 
 impl DirectoryTree {
     pub fn new(files: &[ProcessedFile]) -> Self {
+        let submodules = files.iter().filter_map(|f| f.submodule.clone()).collect();
+        Self::from_annotated(
+            files.iter().map(|f| {
+                let marker = f.change.as_ref().and_then(|c| c.status.tree_marker());
+                (f.path.clone(), marker)
+            }),
+            &submodules,
+        )
+    }
+
+    /// Builds the tree from output paths alone, before any content is processed,
+    /// with an optional marker shown after each file name and the directories
+    /// in `submodules` marked as submodule roots
+    pub fn from_annotated<I>(files: I, submodules: &BTreeSet<String>) -> Self
+    where
+        I: IntoIterator<Item = (String, Option<String>)>,
+    {
+        let (paths, markers): (Vec<String>, Vec<Option<String>>) = files.into_iter().unzip();
+        let markers: HashMap<&str, &str> = paths
+            .iter()
+            .zip(&markers)
+            .filter_map(|(path, marker)| Some((path.as_str(), marker.as_deref()?)))
+            .collect();
         let mut entries = Vec::new();
-        let mut dirs = std::collections::BTreeSet::new();
+        let mut dirs = BTreeSet::new();
 
         // Collect all directory paths
-        for file in files {
-            let path = Path::new(&file.path);
+        for file_path in &paths {
+            let path = Path::new(file_path);
             let ancestors = path.ancestors().skip(1); // Skip the file itself
 
             for ancestor in ancestors {
@@ -44,11 +130,12 @@ impl DirectoryTree {
         }
 
         // Add files
-        for file in files {
-            all_paths.push((file.path.clone(), false));
+        for file_path in &paths {
+            all_paths.push((file_path.clone(), false));
         }
 
-        all_paths.sort_by(|a, b| a.0.cmp(&b.0));
+        // Compare by component so `foo-bar` doesn't sort between `foo` and `foo/a`
+        all_paths.sort_by(|a, b| Path::new(&a.0).cmp(Path::new(&b.0)));
 
         // Build tree structure
         entries.push("+ .".to_string());
@@ -69,7 +156,15 @@ impl DirectoryTree {
                 .map(|n| n.to_string_lossy())
                 .unwrap_or_else(|| path.to_string_lossy());
 
-            entries.push(format!("{}{} {}", indent, symbol, name));
+            let marker = if is_dir {
+                submodules.contains(&path_str).then_some(SUBMODULE_MARKER)
+            } else {
+                markers.get(path_str.as_str()).copied()
+            };
+            match marker {
+                Some(marker) => entries.push(format!("{}{} {} {}", indent, symbol, name, marker)),
+                None => entries.push(format!("{}{} {}", indent, symbol, name)),
+            }
         }
 
         Self { entries }
@@ -82,177 +177,478 @@ impl fmt::Display for DirectoryTree {
     }
 }
 
-pub struct MarkdownWriter;
+/// Returns the code fence language for a file path, or "" if unknown
+pub fn code_language(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+
+    match extension {
+        "rs" => "rust",
+        "py" => "python",
+        "js" => "javascript",
+        "jsx" => "javascript",
+        "ts" => "typescript",
+        "tsx" => "typescript",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" | "sass" => "scss",
+        "less" => "less",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "sh" | "bash" | "zsh" => "bash",
+        "rb" => "ruby",
+        "php" => "php",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" => "c",
+        "h" => "c",
+        "cpp" | "cxx" | "cc" => "cpp",
+        "hpp" | "hxx" | "hh" => "cpp",
+        "cs" => "csharp",
+        "go" => "go",
+        "dart" => "dart",
+        "lua" => "lua",
+        "pl" | "pm" => "perl",
+        "r" => "r",
+        "scala" => "scala",
+        "sql" => "sql",
+        "vb" => "vbnet",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "tf" | "tfvars" => "hcl",
+        "gradle" => "groovy",
+        "dockerfile" => "dockerfile",
+        "ini" => "ini",
+        "bat" | "cmd" => "batch",
+        "ps1" => "powershell",
+        "graphql" | "gql" => "graphql",
+        "proto" => "protobuf",
+        _ => "",
+    }
+}
+
+/// Describes a change for diff section headings, e.g. "renamed from `old.rs`"
+fn change_label(change: &FileChange) -> String {
+    match change.status.old_path() {
+        Some(from) => format!("{} from `{}`", change.status.as_str(), from),
+        None => change.status.as_str().to_string(),
+    }
+}
+
+/// Formats a blame summary's contributors as "Alice (75%), Bob (25%)"
+fn contributor_shares(blame: &BlameSummary) -> String {
+    blame
+        .contributors
+        .iter()
+        .map(|c| {
+            let share = (c.lines * 100 + blame.total_lines / 2) / blame.total_lines.max(1);
+            format!("{} ({}%)", c.name, share)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn commit_json(commit: &CommitSummary) -> Value {
+    json!({
+        "id": commit.id,
+        "author": commit.author,
+        "date": commit.date,
+        "subject": commit.subject
+    })
+}
+
+/// Text content with its original line numbers, when they were tracked
+fn numbered<'a>(file: &ProcessedFile, content: &'a str) -> Cow<'a, str> {
+    match &file.line_numbers {
+        Some(numbers) => Cow::Owned(numbers.render(content)),
+        None => Cow::Borrowed(content),
+    }
+}
+
+/// Escapes text for XML content and attribute values
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Default)]
+pub struct MarkdownWriter {
+    history: Option<Arc<History>>,
+}
+
+impl MarkdownWriter {
+    pub fn new(history: Option<Arc<History>>) -> Self {
+        Self { history }
+    }
+}
 
 impl OutputWriter for MarkdownWriter {
-    fn write_output(&self, files: &[ProcessedFile]) -> Result<String> {
-        let tree = DirectoryTree::new(files);
-        let mut output = String::new();
-
-        output.push_str("## Directory Tree\n");
-        output.push_str("```text\n");
-        output.push_str(&format!("{}", tree));
-        output.push_str("\n```\n\n");
-        output.push_str("---\n\n");
-
-        for file in files {
-            output.push_str(&format!("### `{}`\n\n", file.path));
-
-            match &file.content {
-                FileContent::Text(content) => {
-                    let extension = Path::new(&file.path)
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .unwrap_or("");
-
-                    let language = match extension {
-                        "rs" => "rust",
-                        "py" => "python",
-                        "js" => "javascript",
-                        "jsx" => "javascript",
-                        "ts" => "typescript",
-                        "tsx" => "typescript",
-                        "html" | "htm" => "html",
-                        "css" => "css",
-                        "scss" | "sass" => "scss",
-                        "less" => "less",
-                        "json" => "json",
-                        "yaml" | "yml" => "yaml",
-                        "toml" => "toml",
-                        "xml" => "xml",
-                        "md" | "markdown" => "markdown",
-                        "sh" | "bash" | "zsh" => "bash",
-                        "rb" => "ruby",
-                        "php" => "php",
-                        "java" => "java",
-                        "kt" | "kts" => "kotlin",
-                        "swift" => "swift",
-                        "c" => "c",
-                        "h" => "c",
-                        "cpp" | "cxx" | "cc" => "cpp",
-                        "hpp" | "hxx" | "hh" => "cpp",
-                        "cs" => "csharp",
-                        "go" => "go",
-                        "dart" => "dart",
-                        "lua" => "lua",
-                        "pl" | "pm" => "perl",
-                        "r" => "r",
-                        "scala" => "scala",
-                        "sql" => "sql",
-                        "vb" => "vbnet",
-                        "vue" => "vue",
-                        "svelte" => "svelte",
-                        "tf" | "tfvars" => "hcl",
-                        "gradle" => "groovy",
-                        "dockerfile" => "dockerfile",
-                        "ini" => "ini",
-                        "bat" | "cmd" => "batch",
-                        "ps1" => "powershell",
-                        "graphql" | "gql" => "graphql",
-                        "proto" => "protobuf",
-                        _ => "",
-                    };
-
-                    output.push_str(&format!("```{}\n", language));
-                    output.push_str(content);
-                    output.push_str("\n```\n");
-                }
-                FileContent::Binary(desc)
-                | FileContent::Oversized(desc)
-                | FileContent::Error(desc) => {
-                    output.push_str(desc);
-                }
+    fn write_header(
+        &self,
+        out: &mut dyn Write,
+        tree: &DirectoryTree,
+        part: Option<&OutputPart>,
+    ) -> Result<()> {
+        if let Some(part) = part {
+            write!(out, "# Part {} of {}\n\n", part.number, part.total)?;
+            writeln!(out, "Files in this part:")?;
+            for entry in &part.entries {
+                writeln!(out, "- `{}`", entry)?;
+            }
+            writeln!(out)?;
+        }
+
+        out.write_all(b"## Directory Tree\n")?;
+        out.write_all(b"```text\n")?;
+        write!(out, "{}", tree)?;
+        out.write_all(b"\n```\n\n")?;
+        if let Some(history) = self.history.as_deref().filter(|h| !h.commits.is_empty()) {
+            out.write_all(b"## Git Log\n\n")?;
+            for commit in &history.commits {
+                writeln!(
+                    out,
+                    "- `{}` {} {}: {}",
+                    commit.id, commit.date, commit.author, commit.subject
+                )?;
             }
-            output.push('\n');
-            output.push('\n');
+            out.write_all(b"\n")?;
         }
+        out.write_all(b"---\n\n")?;
+        Ok(())
+    }
+
+    fn write_file(&self, out: &mut dyn Write, _index: usize, file: &ProcessedFile) -> Result<()> {
+        write!(out, "### `{}`\n\n", file.path)?;
+
+        match &file.content {
+            FileContent::Text(content) => {
+                writeln!(out, "```{}", code_language(&file.path))?;
+                out.write_all(numbered(file, content).as_bytes())?;
+                out.write_all(b"\n```\n")?;
+            }
+            FileContent::Binary(desc)
+            | FileContent::Oversized(desc)
+            | FileContent::Error(desc)
+            | FileContent::Omitted(desc) => {
+                out.write_all(desc.as_bytes())?;
+            }
+        }
+        if let Some(blame) = self.history.as_ref().and_then(|h| h.blame.get(&file.path)) {
+            let last = &blame.last_commit;
+            write!(
+                out,
+                "\n\n#### Blame\n\nLast modified in `{}` ({}, {}): {}\n\nTop contributors: {}",
+                last.id,
+                last.date,
+                last.author,
+                last.subject,
+                contributor_shares(blame)
+            )?;
+        }
+        if let Some(change) = &file.change {
+            write!(out, "\n\n#### Diff ({})\n\n```diff\n", change_label(change))?;
+            out.write_all(change.patch.trim_end_matches('\n').as_bytes())?;
+            out.write_all(b"\n```")?;
+        }
+        out.write_all(b"\n\n")?;
+        Ok(())
+    }
+
+    fn write_footer(&self, _out: &mut dyn Write, _file_count: usize) -> Result<()> {
+        Ok(())
+    }
 
-        Ok(output)
+    fn write_index(&self, out: &mut dyn Write, parts: &[OutputPart]) -> Result<()> {
+        out.write_all(b"# Index\n\n| File | Part |\n| --- | --- |\n")?;
+        for part in parts {
+            for entry in &part.entries {
+                writeln!(out, "| `{}` | {} |", entry, part.file_name)?;
+            }
+        }
+        Ok(())
     }
 }
 
-pub struct JsonWriter;
+#[derive(Default)]
+pub struct JsonWriter {
+    history: Option<Arc<History>>,
+}
+
+impl JsonWriter {
+    pub fn new(history: Option<Arc<History>>) -> Self {
+        Self { history }
+    }
+
+    /// Pretty-prints a value nested `depth` levels deep, matching
+    /// `serde_json::to_string_pretty` of the whole document
+    fn nested_pretty(value: &Value, depth: usize) -> Result<String> {
+        let pretty = serde_json::to_string_pretty(value)?;
+        let indent = "  ".repeat(depth);
+        Ok(pretty.replace('\n', &format!("\n{}", indent)))
+    }
+}
 
 impl OutputWriter for JsonWriter {
-    fn write_output(&self, files: &[ProcessedFile]) -> Result<String> {
-        let tree = DirectoryTree::new(files);
+    fn write_header(
+        &self,
+        out: &mut dyn Write,
+        tree: &DirectoryTree,
+        part: Option<&OutputPart>,
+    ) -> Result<()> {
+        out.write_all(b"{\n")?;
+        if let Some(part) = part {
+            let part_json = json!({
+                "number": part.number,
+                "total": part.total,
+                "files": part.entries
+            });
+            writeln!(out, "  \"part\": {},", Self::nested_pretty(&part_json, 1)?)?;
+        }
+        writeln!(
+            out,
+            "  \"directory_tree\": {},",
+            serde_json::to_string(&format!("{}", tree))?
+        )?;
+        if let Some(history) = self.history.as_deref().filter(|h| !h.commits.is_empty()) {
+            let commits: Vec<Value> = history.commits.iter().map(commit_json).collect();
+            writeln!(
+                out,
+                "  \"git_log\": {},",
+                Self::nested_pretty(&json!(commits), 1)?
+            )?;
+        }
+        out.write_all(b"  \"files\": [")?;
+        Ok(())
+    }
 
-        let files_json: Vec<Value> = files
-            .iter()
-            .map(|file| {
-                let content = match &file.content {
-                    FileContent::Text(content) => content.clone(),
-                    FileContent::Binary(desc)
-                    | FileContent::Oversized(desc)
-                    | FileContent::Error(desc) => desc.clone(),
-                };
+    fn write_file(&self, out: &mut dyn Write, index: usize, file: &ProcessedFile) -> Result<()> {
+        let content = match &file.content {
+            FileContent::Text(content) => content,
+            FileContent::Binary(desc)
+            | FileContent::Oversized(desc)
+            | FileContent::Error(desc)
+            | FileContent::Omitted(desc) => desc,
+        };
+
+        let mut file_json = json!({
+            "path": file.path,
+            "content": content
+        });
+        if let (FileContent::Text(_), Some(numbers)) = (&file.content, &file.line_numbers) {
+            file_json["line_numbers"] = numbers.to_json();
+        }
+        if let Some(blame) = self.history.as_ref().and_then(|h| h.blame.get(&file.path)) {
+            let contributors: Vec<Value> = blame
+                .contributors
+                .iter()
+                .map(|c| json!({ "name": c.name, "lines": c.lines }))
+                .collect();
+            file_json["blame"] = json!({
+                "last_commit": commit_json(&blame.last_commit),
+                "contributors": contributors,
+                "lines": blame.total_lines
+            });
+        }
+        if let Some(change) = &file.change {
+            file_json["status"] = json!(change.status.as_str());
+            if let Some(from) = change.status.old_path() {
+                file_json["old_path"] = json!(from);
+            }
+            file_json["diff"] = json!(change.patch);
+        }
+
+        if index > 0 {
+            out.write_all(b",")?;
+        }
+        write!(out, "\n    {}", Self::nested_pretty(&file_json, 2)?)?;
+        Ok(())
+    }
+
+    fn write_footer(&self, out: &mut dyn Write, file_count: usize) -> Result<()> {
+        if file_count > 0 {
+            out.write_all(b"\n  ")?;
+        }
+        out.write_all(b"]\n}")?;
+        Ok(())
+    }
 
+    fn write_index(&self, out: &mut dyn Write, parts: &[OutputPart]) -> Result<()> {
+        let parts_json: Vec<Value> = parts
+            .iter()
+            .map(|part| {
                 json!({
-                    "path": file.path,
-                    "content": content
+                    "number": part.number,
+                    "file": part.file_name,
+                    "files": part.entries
                 })
             })
             .collect();
+        serde_json::to_writer_pretty(&mut *out, &json!({ "parts": parts_json }))?;
+        Ok(())
+    }
+}
 
-        let output = json!({
-            "directory_tree": format!("{}", tree),
-            "files": files_json
-        });
+#[derive(Default)]
+pub struct XmlWriter {
+    history: Option<Arc<History>>,
+}
 
-        let json_str = serde_json::to_string_pretty(&output)?;
-        Ok(json_str)
+impl XmlWriter {
+    pub fn new(history: Option<Arc<History>>) -> Self {
+        Self { history }
     }
 }
 
-pub struct XmlWriter;
-
 impl OutputWriter for XmlWriter {
-    fn write_output(&self, files: &[ProcessedFile]) -> Result<String> {
-        let tree = DirectoryTree::new(files);
-        let mut output = String::new();
-
-        output.push_str(r#"<instructions>Read all code before answering.</instructions>"#);
-        output.push('\n');
-        output.push('\n');
-
-        output.push_str("<directory_tree>\n");
-        output.push_str(&format!("{}", tree));
-        output.push_str("\n</directory_tree>\n\n");
-
-        for file in files {
-            match &file.content {
-                FileContent::Text(content) => {
-                    output.push_str(&format!(r#"<file path="{}">"#, file.path));
-                    output.push('\n');
-                    output.push_str(content);
-                    output.push_str("\n</file>");
-                }
-                FileContent::Binary(desc)
-                | FileContent::Oversized(desc)
-                | FileContent::Error(desc) => {
-                    output.push_str(&format!(r#"<file path="{}">{}</file>"#, file.path, desc));
-                }
+    fn write_header(
+        &self,
+        out: &mut dyn Write,
+        tree: &DirectoryTree,
+        part: Option<&OutputPart>,
+    ) -> Result<()> {
+        if let Some(part) = part {
+            writeln!(
+                out,
+                "<part number=\"{}\" total=\"{}\">",
+                part.number, part.total
+            )?;
+            for entry in &part.entries {
+                writeln!(out, "<entry>{}</entry>", xml_escape(entry))?;
+            }
+            out.write_all(b"</part>\n\n")?;
+        }
+
+        out.write_all(b"<instructions>Read all code before answering.</instructions>")?;
+        out.write_all(b"\n\n")?;
+
+        out.write_all(b"<directory_tree>\n")?;
+        write!(out, "{}", xml_escape(&tree.to_string()))?;
+        out.write_all(b"\n</directory_tree>\n\n")?;
+        if let Some(history) = self.history.as_deref().filter(|h| !h.commits.is_empty()) {
+            out.write_all(b"<git_log>\n")?;
+            for commit in &history.commits {
+                writeln!(
+                    out,
+                    r#"<commit id="{}" author="{}" date="{}">{}</commit>"#,
+                    commit.id,
+                    xml_escape(&commit.author),
+                    commit.date,
+                    xml_escape(&commit.subject)
+                )?;
+            }
+            out.write_all(b"</git_log>\n\n")?;
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, out: &mut dyn Write, _index: usize, file: &ProcessedFile) -> Result<()> {
+        match &file.content {
+            FileContent::Text(content) => {
+                writeln!(out, r#"<file path="{}">"#, xml_escape(&file.path))?;
+                out.write_all(numbered(file, content).as_bytes())?;
+                out.write_all(b"\n</file>")?;
+            }
+            FileContent::Binary(desc)
+            | FileContent::Oversized(desc)
+            | FileContent::Error(desc)
+            | FileContent::Omitted(desc) => {
+                write!(
+                    out,
+                    r#"<file path="{}">{}</file>"#,
+                    xml_escape(&file.path),
+                    desc
+                )?;
             }
-            output.push('\n');
-            output.push('\n');
         }
+        if let Some(blame) = self.history.as_ref().and_then(|h| h.blame.get(&file.path)) {
+            let last = &blame.last_commit;
+            write!(
+                out,
+                r#"
+<blame path="{}" lines="{}">
+<last_commit id="{}" author="{}" date="{}">{}</last_commit>"#,
+                xml_escape(&file.path),
+                blame.total_lines,
+                last.id,
+                xml_escape(&last.author),
+                last.date,
+                xml_escape(&last.subject)
+            )?;
+            for contributor in &blame.contributors {
+                write!(
+                    out,
+                    r#"
+<contributor name="{}" lines="{}"/>"#,
+                    xml_escape(&contributor.name),
+                    contributor.lines
+                )?;
+            }
+            out.write_all(b"\n</blame>")?;
+        }
+        if let Some(change) = &file.change {
+            write!(
+                out,
+                r#"
+<diff path="{}" status="{}""#,
+                xml_escape(&file.path),
+                change.status.as_str()
+            )?;
+            if let Some(from) = change.status.old_path() {
+                write!(out, r#" from="{}""#, xml_escape(from))?;
+            }
+            out.write_all(b">\n")?;
+            out.write_all(change.patch.trim_end_matches('\n').as_bytes())?;
+            out.write_all(b"\n</diff>")?;
+        }
+        out.write_all(b"\n\n")?;
+        Ok(())
+    }
+
+    fn write_footer(&self, _out: &mut dyn Write, _file_count: usize) -> Result<()> {
+        Ok(())
+    }
 
-        Ok(output)
+    fn write_index(&self, out: &mut dyn Write, parts: &[OutputPart]) -> Result<()> {
+        out.write_all(b"<index>\n")?;
+        for part in parts {
+            writeln!(
+                out,
+                "<part number=\"{}\" file=\"{}\">",
+                part.number,
+                xml_escape(&part.file_name)
+            )?;
+            for entry in &part.entries {
+                writeln!(out, "<entry>{}</entry>", xml_escape(entry))?;
+            }
+            out.write_all(b"</part>\n")?;
+        }
+        out.write_all(b"</index>\n")?;
+        Ok(())
     }
 }
 
-pub fn get_writer(format: &str) -> Box<dyn OutputWriter> {
+/// Picks the writer for a format; `history` adds the git log and blame sections
+pub fn get_writer(format: &str, history: Option<Arc<History>>) -> Box<dyn OutputWriter> {
     match format {
-        "md" => Box::new(MarkdownWriter),
-        "json" => Box::new(JsonWriter),
-        "xml" => Box::new(XmlWriter),
-        _ => Box::new(MarkdownWriter), // Default fallback
+        "md" => Box::new(MarkdownWriter::new(history)),
+        "json" => Box::new(JsonWriter::new(history)),
+        "xml" => Box::new(XmlWriter::new(history)),
+        _ => Box::new(MarkdownWriter::new(history)), // Default fallback
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lines::LineNumbers;
     use crate::processor::{FileContent, ProcessedFile};
 
     fn create_test_files() -> Vec<ProcessedFile> {
@@ -262,14 +658,23 @@ mod tests {
                 content: FileContent::Text(
                     "fn main() {\n    println!(\"Hello, world!\");\n}".to_string(),
                 ),
+                change: None,
+                submodule: None,
+                line_numbers: None,
             },
             ProcessedFile {
                 path: "README.md".to_string(),
                 content: FileContent::Text("# Test Project\n\nThis is a test.".to_string()),
+                change: None,
+                submodule: None,
+                line_numbers: None,
             },
             ProcessedFile {
                 path: "assets/logo.png".to_string(),
                 content: FileContent::Binary("[binary skipped]".to_string()),
+                change: None,
+                submodule: None,
+                line_numbers: None,
             },
         ]
     }
@@ -288,10 +693,22 @@ mod tests {
         assert!(tree_str.contains("- logo.png"));
     }
 
+    #[test]
+    fn test_directory_tree_sibling_order() {
+        let tree = DirectoryTree::from_annotated(
+            ["common-2/y.rs", "common/x.rs"].map(|path| (path.to_string(), None)),
+            &BTreeSet::new(),
+        );
+        assert_eq!(
+            tree.to_string(),
+            "+ .\n+ common\n  - x.rs\n+ common-2\n  - y.rs"
+        );
+    }
+
     #[test]
     fn test_markdown_writer() -> Result<()> {
         let files = create_test_files();
-        let writer = MarkdownWriter;
+        let writer = MarkdownWriter::default();
 
         let result = writer.write_output(&files)?;
 
@@ -307,7 +724,7 @@ mod tests {
     #[test]
     fn test_json_writer() -> Result<()> {
         let files = create_test_files();
-        let writer = JsonWriter;
+        let writer = JsonWriter::default();
 
         let result = writer.write_output(&files)?;
 
@@ -320,10 +737,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_line_numbers() -> Result<()> {
+        let mut files = create_test_files();
+        files[0].line_numbers = Some(LineNumbers::new("fn main() {\n", 9));
+        files[0].content = FileContent::Text("fn main() {\n".to_string());
+
+        let result = MarkdownWriter::default().write_output(&files)?;
+        assert!(result.contains("```rust\n9 | fn main() {\n\n```"));
+        let result = XmlWriter::default().write_output(&files)?;
+        assert!(result.contains("<file path=\"src/main.rs\">\n9 | fn main() {\n"));
+
+        // JSON keeps the content as is, with the numbers alongside
+        let parsed: Value = serde_json::from_str(&JsonWriter::default().write_output(&files)?)?;
+        assert_eq!(parsed["files"][0]["content"], "fn main() {\n");
+        assert_eq!(parsed["files"][0]["line_numbers"], json!([9]));
+        assert!(parsed["files"][1].get("line_numbers").is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_xml_writer() -> Result<()> {
         let files = create_test_files();
-        let writer = XmlWriter;
+        let writer = XmlWriter::default();
 
         let result = writer.write_output(&files)?;
 
@@ -335,4 +772,151 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_xml_escapes_paths() -> Result<()> {
+        let writer = XmlWriter::default();
+        let part = OutputPart {
+            number: 1,
+            total: 1,
+            file_name: "R&D.001.xml".to_string(),
+            entries: vec!["docs/Q&A <draft>.md".to_string()],
+        };
+
+        let files = vec![ProcessedFile {
+            path: "docs/Q&A <draft>.md".to_string(),
+            content: FileContent::Text("# Q&A".to_string()),
+            change: None,
+            submodule: None,
+            line_numbers: None,
+        }];
+        let result = writer.write_part(&part, &files)?;
+        assert!(result.contains("<entry>docs/Q&amp;A &lt;draft&gt;.md</entry>"));
+        assert!(result.contains("- Q&amp;A &lt;draft&gt;.md"));
+
+        let mut index = Vec::new();
+        writer.write_index(&mut index, &[part])?;
+        let index = String::from_utf8(index).unwrap();
+        assert!(index.contains(r#"<part number="1" file="R&amp;D.001.xml">"#));
+        assert!(index.contains("<entry>docs/Q&amp;A &lt;draft&gt;.md</entry>"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_sections() -> Result<()> {
+        use crate::diff::{ChangeStatus, FileChange};
+
+        let files = vec![
+            ProcessedFile {
+                path: "src/new.rs".to_string(),
+                content: FileContent::Text("fn new() {}".to_string()),
+                submodule: None,
+                line_numbers: None,
+                change: Some(FileChange {
+                    status: ChangeStatus::Renamed {
+                        from: "src/old.rs".to_string(),
+                    },
+                    patch: "-fn old() {}\n+fn new() {}\n".to_string(),
+                }),
+            },
+            ProcessedFile {
+                path: "Q&A.txt".to_string(),
+                content: FileContent::Omitted("[deleted]".to_string()),
+                submodule: None,
+                line_numbers: None,
+                change: Some(FileChange {
+                    status: ChangeStatus::Deleted,
+                    patch: "-bye\n".to_string(),
+                }),
+            },
+        ];
+
+        let tree = format!("{}", DirectoryTree::new(&files));
+        assert!(tree.contains("- Q&A.txt (deleted)"));
+        assert!(tree.contains("- new.rs (renamed from src/old.rs)"));
+
+        let markdown = MarkdownWriter::default().write_output(&files)?;
+        assert!(markdown.contains(
+            "#### Diff (renamed from `src/old.rs`)\n\n```diff\n-fn old() {}\n+fn new() {}\n```"
+        ));
+
+        let json: Value = serde_json::from_str(&JsonWriter::default().write_output(&files)?)?;
+        assert_eq!(json["files"][0]["status"], "renamed");
+        assert_eq!(json["files"][0]["old_path"], "src/old.rs");
+        assert_eq!(json["files"][1]["diff"], "-bye\n");
+
+        let xml = XmlWriter::default().write_output(&files)?;
+        assert!(xml.contains(r#"<diff path="src/new.rs" status="renamed" from="src/old.rs">"#));
+        assert!(xml.contains(r#"<diff path="Q&amp;A.txt" status="deleted">"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_sections() -> Result<()> {
+        use crate::history::{BlameSummary, CommitSummary, Contributor, History};
+
+        let commit = CommitSummary {
+            id: "abc1234".to_string(),
+            author: "Alice".to_string(),
+            date: "2024-05-01".to_string(),
+            subject: "Say <hello>".to_string(),
+        };
+        let mut history = History {
+            commits: vec![commit.clone()],
+            ..History::default()
+        };
+        history.blame.insert(
+            "src/main.rs".to_string(),
+            BlameSummary {
+                last_commit: commit,
+                contributors: vec![
+                    Contributor {
+                        name: "Alice".to_string(),
+                        lines: 3,
+                    },
+                    Contributor {
+                        name: "Bob".to_string(),
+                        lines: 1,
+                    },
+                ],
+                total_lines: 4,
+            },
+        );
+        let blame = history.blame["src/main.rs"].clone();
+        history.blame.insert("src/a&b.rs".to_string(), blame);
+        let history = Some(Arc::new(history));
+        let mut files = create_test_files();
+        files.push(ProcessedFile {
+            path: "src/a&b.rs".to_string(),
+            content: FileContent::Text("fn ab() {}".to_string()),
+            change: None,
+            submodule: None,
+            line_numbers: None,
+        });
+
+        let markdown = MarkdownWriter::new(history.clone()).write_output(&files)?;
+        assert!(markdown.contains("## Git Log\n\n- `abc1234` 2024-05-01 Alice: Say <hello>\n"));
+        assert!(markdown.contains("#### Blame\n\nLast modified in `abc1234` (2024-05-01, Alice)"));
+        assert!(markdown.contains("Top contributors: Alice (75%), Bob (25%)"));
+
+        let json: Value =
+            serde_json::from_str(&JsonWriter::new(history.clone()).write_output(&files)?)?;
+        assert_eq!(json["git_log"][0]["id"], "abc1234");
+        assert_eq!(json["files"][0]["blame"]["last_commit"]["author"], "Alice");
+        assert_eq!(json["files"][0]["blame"]["contributors"][1]["lines"], 1);
+        assert!(json["files"][1].get("blame").is_none());
+
+        let xml = XmlWriter::new(history).write_output(&files)?;
+        assert!(xml.contains(
+            r#"<commit id="abc1234" author="Alice" date="2024-05-01">Say &lt;hello&gt;</commit>"#
+        ));
+        assert!(xml.contains(r#"<blame path="src/main.rs" lines="4">"#));
+        assert!(xml.contains(r#"<file path="src/a&amp;b.rs">"#));
+        assert!(xml.contains(r#"<blame path="src/a&amp;b.rs" lines="4">"#));
+        assert!(xml.contains(r#"<contributor name="Bob" lines="1"/>"#));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,256 @@
+use crate::{
+    error::Result,
+    output::{DirectoryTree, OutputWriter},
+    processor::{FileLog, ProcessedFile, Processor},
+    tokenizer::Tokenizer,
+    walker::FileEntry,
+};
+use rayon::prelude::*;
+use std::{io::Write, path::Path, thread};
+use tracing::debug;
+
+/// Processed files allowed to wait for the writer before processing blocks
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Files handed to the thread pool at once, per thread
+const BATCH_PER_THREAD: usize = 4;
+
+/// Normalize path to use forward slashes regardless of platform
+fn normalize_path_separators<P: AsRef<Path>>(path: P) -> String {
+    path.as_ref().to_string_lossy().replace('\\', "/")
+}
+
+/// Totals gathered while streaming a document
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StreamStats {
+    pub files: usize,
+    pub bytes: usize,
+    pub tokens: usize,
+}
+
+fn build_pool(threads: usize) -> Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .build()
+        .map_err(|e| std::io::Error::other(e).into())
+}
+
+/// Processes every file across `threads` workers, keeping the walk order
+///
+/// Each file's log lines are emitted together once all files are processed.
+pub fn process_all(
+    entries: &[FileEntry],
+    processor: &Processor,
+    threads: usize,
+) -> Result<Vec<ProcessedFile>> {
+    let pool = build_pool(threads)?;
+    let results: Vec<(ProcessedFile, FileLog)> = pool.install(|| {
+        entries
+            .par_iter()
+            .map(|entry| processor.process_entry(entry))
+            .collect()
+    });
+
+    Ok(results
+        .into_iter()
+        .map(|(file, log)| {
+            log.emit();
+            file
+        })
+        .collect())
+}
+
+/// Processes files and writes them to `out` as they become ready
+///
+/// The header and directory tree are written from the walked paths before any
+/// content is read. Files are processed in small batches across `threads`
+/// workers and handed to the writer in walk order through a bounded channel,
+/// so only a few batches of file contents are held in memory at once
+/// regardless of repository size. Each file's log lines are emitted as it is
+/// written.
+pub fn stream(
+    entries: &[FileEntry],
+    processor: &Processor,
+    writer: &dyn OutputWriter,
+    tokenizer: &Tokenizer,
+    threads: usize,
+    out: &mut dyn Write,
+) -> Result<StreamStats> {
+    let pool = build_pool(threads)?;
+    let batch_size = threads.max(1) * BATCH_PER_THREAD;
+
+    let mut stats = StreamStats::default();
+    let mut buf = Vec::new();
+
+    let submodules = entries.iter().filter_map(|e| e.submodule.clone()).collect();
+    let tree = DirectoryTree::from_annotated(
+        entries.iter().map(|e| {
+            let marker = e.change.as_ref().and_then(|c| c.status.tree_marker());
+            (normalize_path_separators(&e.path), marker)
+        }),
+        &submodules,
+    );
+    writer.write_header(&mut buf, &tree, None)?;
+    emit(out, &mut buf, tokenizer, &mut stats)?;
+
+    thread::scope(|scope| -> Result<()> {
+        let (tx, rx) = crossbeam_channel::bounded::<(ProcessedFile, FileLog)>(CHANNEL_CAPACITY);
+
+        scope.spawn(move || {
+            for batch in entries.chunks(batch_size) {
+                let results: Vec<(ProcessedFile, FileLog)> = pool.install(|| {
+                    batch
+                        .par_iter()
+                        .map(|entry| processor.process_entry(entry))
+                        .collect()
+                });
+                for result in results {
+                    // The receiver is gone once writing fails; stop processing
+                    if tx.send(result).is_err() {
+                        debug!("Output closed, stopping file processing");
+                        return;
+                    }
+                }
+            }
+        });
+
+        for (index, (file, log)) in rx.iter().enumerate() {
+            log.emit();
+            writer.write_file(&mut buf, index, &file)?;
+            emit(out, &mut buf, tokenizer, &mut stats)?;
+            stats.files += 1;
+        }
+        Ok(())
+    })?;
+
+    writer.write_footer(&mut buf, stats.files)?;
+    emit(out, &mut buf, tokenizer, &mut stats)?;
+    out.flush()?;
+
+    Ok(stats)
+}
+
+/// Counts and writes a rendered chunk, then clears it for reuse
+fn emit(
+    out: &mut dyn Write,
+    buf: &mut Vec<u8>,
+    tokenizer: &Tokenizer,
+    stats: &mut StreamStats,
+) -> Result<()> {
+    stats.bytes += buf.len();
+    stats.tokens += tokenizer.count(&String::from_utf8_lossy(buf));
+    out.write_all(buf)?;
+    buf.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        output::{JsonWriter, MarkdownWriter, XmlWriter},
+    };
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn create_entries(dir: &TempDir) -> Result<Vec<FileEntry>> {
+        std::fs::create_dir_all(dir.path().join("src"))?;
+        std::fs::write(dir.path().join("README.md"), "# Test\n")?;
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}\n")?;
+        std::fs::write(dir.path().join("src/data.bin"), [0u8, 1, 2, 0, 255])?;
+
+        Ok(["README.md", "src/data.bin", "src/main.rs"]
+            .iter()
+            .map(|path| {
+                let absolute_path = dir.path().join(path);
+                FileEntry {
+                    path: PathBuf::from(path),
+                    size: std::fs::metadata(&absolute_path).unwrap().len(),
+                    absolute_path,
+                    is_binary: false,
+                    is_oversized: false,
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+
+    #[test]
+    fn test_stream_matches_in_memory_render() -> Result<()> {
+        let dir = TempDir::new()?;
+        let entries = create_entries(&dir)?;
+        let processor = Processor::new(Config::default()).unwrap();
+        let files = process_all(&entries, &processor, 1)?;
+
+        let writers: Vec<Box<dyn OutputWriter>> = vec![
+            Box::new(MarkdownWriter::default()),
+            Box::new(JsonWriter::default()),
+            Box::new(XmlWriter::default()),
+        ];
+        for writer in writers {
+            for threads in [1, 4] {
+                let mut out = Vec::new();
+                let stats = stream(
+                    &entries,
+                    &processor,
+                    writer.as_ref(),
+                    &Tokenizer::Heuristic,
+                    threads,
+                    &mut out,
+                )?;
+
+                assert_eq!(String::from_utf8_lossy(&out), writer.write_output(&files)?);
+                assert_eq!(stats.files, 3);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_empty() -> Result<()> {
+        let processor = Processor::new(Config::default()).unwrap();
+        let mut out = Vec::new();
+        let stats = stream(
+            &[],
+            &processor,
+            &JsonWriter::default(),
+            &Tokenizer::Heuristic,
+            1,
+            &mut out,
+        )?;
+
+        let json: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(json["files"], serde_json::json!([]));
+        assert_eq!(stats.files, 0);
+        assert_eq!(stats.bytes, out.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_all_keeps_walk_order() -> Result<()> {
+        let dir = TempDir::new()?;
+        let entries: Vec<FileEntry> = (0..50)
+            .map(|i| {
+                let path = format!("file{:02}.txt", i);
+                let absolute_path = dir.path().join(&path);
+                std::fs::write(&absolute_path, "x".repeat(i * 100)).unwrap();
+                FileEntry {
+                    path: PathBuf::from(path),
+                    size: (i * 100) as u64,
+                    absolute_path,
+                    is_binary: false,
+                    is_oversized: false,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let processor = Processor::new(Config::default()).unwrap();
+
+        let files = process_all(&entries, &processor, 8)?;
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        let expected: Vec<String> = (0..50).map(|i| format!("file{:02}.txt", i)).collect();
+        assert_eq!(paths, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{archive::ArchiveKind, git, tree, walker::FileEntry};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Used when a source has no usable directory or repository name
+const FALLBACK_ROOT: &str = "source";
+
+/// Normalize path to use forward slashes regardless of platform
+fn normalize_path_separators<P: AsRef<Path>>(path: P) -> String {
+    path.as_ref().to_string_lossy().replace('\\', "/")
+}
+
+/// Names the root directory each source's files are listed under when several
+/// sources are combined, in the order given
+///
+/// Local paths are named after their directory, archives after their file name
+/// without the suffix, and remote URLs after the repository. Repeated names
+/// get a numeric suffix, so `a/common` and `b/common` become `common` and
+/// `common-2`.
+pub fn root_names(sources: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    sources
+        .iter()
+        .map(|source| {
+            let base = base_name(source);
+            let mut name = base.clone();
+            let mut n = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{}-{}", base, n);
+                n += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+fn base_name(source: &str) -> String {
+    let name = if git::is_remote_source(source) {
+        let url = git::parse_git_source(source).url;
+        // `file:///work/repo/.git` is named after `repo`
+        let url = url.trim_end_matches('/');
+        let url = url.strip_suffix("/.git").unwrap_or(url);
+        url.rsplit(['/', ':'])
+            .next()
+            .map(|name| name.strip_suffix(".git").unwrap_or(name).to_string())
+    } else {
+        let path = tree::parse_tree_source(source)
+            .map(|tree_source| tree_source.path)
+            .unwrap_or_else(|| PathBuf::from(source));
+        // Canonicalize so `.` and `..` name the directory they point at; a
+        // single file is named after its directory
+        let path = path.canonicalize().unwrap_or(path);
+        if let Some(stem) = ArchiveKind::stem(&path).filter(|_| path.is_file()) {
+            return stem;
+        }
+        let dir = if path.is_file() {
+            path.parent().unwrap_or(&path)
+        } else {
+            &path
+        };
+        dir.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+    name.filter(|name| !name.is_empty())
+        .unwrap_or_else(|| FALLBACK_ROOT.to_string())
+}
+
+/// Moves entries under `root` in the output, including the submodule roots
+/// recorded on them
+///
+/// Paths of a plain local walk start with the path that was walked, given as
+/// `base`, which `root` replaces; other paths are already relative.
+pub fn prefix(root: &str, base: Option<&Path>, files: &mut [FileEntry]) {
+    for entry in files.iter_mut() {
+        let relative = |path: &Path| -> PathBuf {
+            let path = base.map_or(path, |base| path.strip_prefix(base).unwrap_or(path));
+            Path::new(root).join(path)
+        };
+        entry.path = relative(&entry.path);
+        if let Some(submodule) = entry.submodule.as_mut() {
+            *submodule = normalize_path_separators(relative(Path::new(submodule)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn names(sources: &[&str]) -> Vec<String> {
+        let sources: Vec<String> = sources.iter().map(|s| s.to_string()).collect();
+        root_names(&sources)
+    }
+
+    #[test]
+    fn test_root_names() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        for path in ["svc-a", "a/common", "b/common"] {
+            fs::create_dir_all(root.join(path)).unwrap();
+        }
+        let local = |path: &str| root.join(path).to_string_lossy().into_owned();
+
+        assert_eq!(
+            names(&[
+                &local("svc-a"),
+                &local("a/common"),
+                &local("b/common"),
+                "https://github.com/org/proto.git@v3#api",
+                "git@github.com:org/tools.git",
+                "https://gitlab.com/org/common/",
+                "file:///work/mirror/.git",
+            ]),
+            vec!["svc-a", "common", "common-2", "proto", "tools", "common-3", "mirror"]
+        );
+        fs::write(root.join("svc-a/Cargo.toml"), "").unwrap();
+        fs::write(root.join("a/repro-42.tar.gz"), "").unwrap();
+        assert_eq!(
+            names(&[
+                &local("svc-a/."),
+                &local("a/common/.."),
+                &local("svc-a/Cargo.toml"),
+                &local("a/repro-42.tar.gz")
+            ]),
+            vec!["svc-a", "a", "svc-a-2", "repro-42"]
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        let entry = |path: &str, submodule: Option<&str>| FileEntry {
+            path: PathBuf::from(path),
+            submodule: submodule.map(str::to_string),
+            ..Default::default()
+        };
+
+        let mut files = vec![entry("vendor/lib/src/lib.rs", Some("vendor/lib"))];
+        prefix("proto", None, &mut files);
+        assert_eq!(files[0].path, Path::new("proto/vendor/lib/src/lib.rs"));
+        assert_eq!(files[0].submodule.as_deref(), Some("proto/vendor/lib"));
+
+        let mut files = vec![
+            entry("/work/svc-a/src/main.rs", None),
+            entry(
+                "/work/svc-a/vendor/lib/lib.rs",
+                Some("/work/svc-a/vendor/lib"),
+            ),
+        ];
+        prefix("svc-a", Some(Path::new("/work/svc-a")), &mut files);
+        assert_eq!(files[0].path, Path::new("svc-a/src/main.rs"));
+        assert_eq!(files[1].path, Path::new("svc-a/vendor/lib/lib.rs"));
+        assert_eq!(files[1].submodule.as_deref(), Some("svc-a/vendor/lib"));
+    }
+}
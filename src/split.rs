@@ -0,0 +1,406 @@
+use crate::{
+    error::Result,
+    output::{OutputPart, OutputWriter},
+    processor::{FileContent, ProcessedFile},
+    tokenizer::Tokenizer,
+};
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// Maximum size of each part of a split output
+#[derive(Debug, Clone, Copy)]
+pub enum SplitLimit {
+    Tokens(usize),
+    Bytes(usize),
+}
+
+impl SplitLimit {
+    fn max(&self) -> usize {
+        match self {
+            SplitLimit::Tokens(n) | SplitLimit::Bytes(n) => *n,
+        }
+    }
+
+    fn measure(&self, tokenizer: &Tokenizer, text: &str) -> usize {
+        match self {
+            SplitLimit::Tokens(_) => tokenizer.count(text),
+            SplitLimit::Bytes(_) => text.len(),
+        }
+    }
+}
+
+/// A rendered part, ready to be written to `part.file_name` next to the output file
+#[derive(Debug, Clone)]
+pub struct RenderedPart {
+    pub part: OutputPart,
+    pub content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitOutput {
+    pub parts: Vec<RenderedPart>,
+    pub index_file_name: String,
+    pub index: String,
+}
+
+/// A whole file, or a line range of a file too large for one part
+#[derive(Debug, Clone)]
+struct Piece {
+    file: ProcessedFile,
+    entry: String,
+}
+
+/// Builds the file name for a part: `out/dump.md` with label `001` becomes `dump.001.md`
+pub fn part_file_name(out: &str, label: &str) -> String {
+    let path = Path::new(out);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| out.to_string());
+    match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, label, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, label),
+    }
+}
+
+/// Splits processed files into numbered parts that each fit within `limit`
+///
+/// Files are kept whole and in order; only a file that cannot fit in a part on
+/// its own is cut into line ranges. Every part is rendered with `writer`, so
+/// each one is a complete document in the chosen format with a header naming
+/// the part and the files it contains.
+pub fn split(
+    files: &[ProcessedFile],
+    writer: &dyn OutputWriter,
+    tokenizer: &Tokenizer,
+    limit: SplitLimit,
+    out: &str,
+) -> Result<SplitOutput> {
+    let splitter = Splitter {
+        writer,
+        tokenizer,
+        limit,
+        out,
+    };
+
+    let mut pieces = Vec::new();
+    for file in files {
+        pieces.extend(splitter.pieces_for(file)?);
+    }
+
+    let chunks = splitter.group(pieces)?;
+    let total = chunks.len();
+
+    let mut parts = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let part = splitter.part(i + 1, total, chunk);
+        let files: Vec<ProcessedFile> = chunk.iter().map(|p| p.file.clone()).collect();
+        let content = writer.write_part(&part, &files)?;
+        let size = limit.measure(tokenizer, &content);
+        if size > limit.max() {
+            warn!(
+                "Part {} exceeds the split limit ({} > {}): a single line is larger than a part",
+                part.number,
+                size,
+                limit.max()
+            );
+        }
+        parts.push(RenderedPart { part, content });
+    }
+
+    let index_parts: Vec<OutputPart> = parts.iter().map(|p| p.part.clone()).collect();
+    let mut index = Vec::new();
+    writer.write_index(&mut index, &index_parts)?;
+    Ok(SplitOutput {
+        index: String::from_utf8_lossy(&index).into_owned(),
+        index_file_name: part_file_name(out, "index"),
+        parts,
+    })
+}
+
+struct Splitter<'a> {
+    writer: &'a dyn OutputWriter,
+    tokenizer: &'a Tokenizer,
+    limit: SplitLimit,
+    out: &'a str,
+}
+
+impl Splitter<'_> {
+    fn measure(&self, text: &str) -> usize {
+        self.limit.measure(self.tokenizer, text)
+    }
+
+    fn part(&self, number: usize, total: usize, pieces: &[Piece]) -> OutputPart {
+        OutputPart {
+            number,
+            total,
+            file_name: part_file_name(self.out, &format!("{:03}", number)),
+            entries: pieces.iter().map(|p| p.entry.clone()).collect(),
+        }
+    }
+
+    fn cost(&self, number: usize, total: usize, pieces: &[Piece]) -> Result<usize> {
+        let part = self.part(number, total, pieces);
+        let files: Vec<ProcessedFile> = pieces.iter().map(|p| p.file.clone()).collect();
+        Ok(self.measure(&self.writer.write_part(&part, &files)?))
+    }
+
+    /// Returns the file as one piece, or as line ranges if it cannot fit in a part alone
+    fn pieces_for(&self, file: &ProcessedFile) -> Result<Vec<Piece>> {
+        let whole = Piece {
+            file: file.clone(),
+            entry: file.path.clone(),
+        };
+        let text = match &file.content {
+            FileContent::Text(text) => text,
+            _ => return Ok(vec![whole]),
+        };
+        if self.cost(1, 1, std::slice::from_ref(&whole))? <= self.limit.max() {
+            return Ok(vec![whole]);
+        }
+
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        debug!(
+            "Splitting {} ({} lines) across parts",
+            file.path,
+            lines.len()
+        );
+
+        let piece_for = |start: usize, end: usize| {
+            let text = lines[start..end].concat();
+            Piece {
+                file: ProcessedFile {
+                    path: file.path.clone(),
+                    content: FileContent::Text(
+                        text.strip_suffix('\n').unwrap_or(&text).to_string(),
+                    ),
+                    // The diff goes with the last range so it follows the full new content
+                    change: if end == lines.len() {
+                        file.change.clone()
+                    } else {
+                        None
+                    },
+                    submodule: file.submodule.clone(),
+                    line_numbers: file
+                        .line_numbers
+                        .as_ref()
+                        .map(|numbers| numbers.slice(start..end)),
+                },
+                entry: format!(
+                    "{} (lines {}-{} of {})",
+                    file.path,
+                    start + 1,
+                    end,
+                    lines.len()
+                ),
+            }
+        };
+
+        let base = self.cost(1, 1, &[piece_for(0, 0)])?;
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < lines.len() {
+            // Estimate how many lines fit, then trim until the rendered piece does
+            let mut end = start;
+            let mut used = base;
+            while end < lines.len() {
+                let line_cost = self.measure(lines[end]);
+                if end > start && used + line_cost > self.limit.max() {
+                    break;
+                }
+                used += line_cost;
+                end += 1;
+            }
+            while end > start + 1 && self.cost(1, 1, &[piece_for(start, end)])? > self.limit.max() {
+                end -= 1;
+            }
+
+            pieces.push(piece_for(start, end));
+            start = end;
+        }
+
+        Ok(pieces)
+    }
+
+    /// Greedily packs pieces into parts, then re-renders each part with its
+    /// final numbering and moves trailing pieces forward until every part fits
+    fn group(&self, pieces: Vec<Piece>) -> Result<Vec<Vec<Piece>>> {
+        let empty = self.measure(&self.writer.write_output(&[])?);
+        let base = self.cost(1, 1, &[])?;
+
+        let mut chunks: Vec<Vec<Piece>> = Vec::new();
+        let mut current = Vec::new();
+        let mut used = base;
+        for piece in pieces {
+            let rendered = self
+                .measure(
+                    &self
+                        .writer
+                        .write_output(std::slice::from_ref(&piece.file))?,
+                )
+                .saturating_sub(empty);
+            let cost = rendered + self.measure(&piece.entry);
+            if !current.is_empty() && used + cost > self.limit.max() {
+                chunks.push(std::mem::take(&mut current));
+                used = base;
+            }
+            used += cost;
+            current.push(piece);
+        }
+        if !current.is_empty() || chunks.is_empty() {
+            chunks.push(current);
+        }
+
+        loop {
+            let mut changed = false;
+            let mut i = 0;
+            while i < chunks.len() {
+                let total = chunks.len();
+                if chunks[i].len() > 1 && self.cost(i + 1, total, &chunks[i])? > self.limit.max() {
+                    let piece = chunks[i].pop().expect("chunk has more than one piece");
+                    if i + 1 == chunks.len() {
+                        chunks.push(Vec::new());
+                    }
+                    chunks[i + 1].insert(0, piece);
+                    changed = true;
+                } else {
+                    i += 1;
+                }
+            }
+            if !changed {
+                return Ok(chunks);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{JsonWriter, MarkdownWriter, XmlWriter};
+
+    fn text(path: &str, content: &str) -> ProcessedFile {
+        ProcessedFile {
+            path: path.to_string(),
+            content: FileContent::Text(content.to_string()),
+            change: None,
+            submodule: None,
+            line_numbers: None,
+        }
+    }
+
+    fn create_test_files() -> Vec<ProcessedFile> {
+        let mut files: Vec<ProcessedFile> = (0..12)
+            .map(|i| {
+                text(
+                    &format!("src/mod{:02}.rs", i),
+                    &format!("pub fn f{}() {{}}\n", i).repeat(20),
+                )
+            })
+            .collect();
+        files.push(text(
+            "src/zz_huge.rs",
+            &(0..400)
+                .map(|i| format!("const LINE_{}: u32 = {};", i, i))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ));
+        files
+    }
+
+    #[test]
+    fn test_part_file_name() {
+        assert_eq!(part_file_name("dump.md", "001"), "dump.001.md");
+        assert_eq!(part_file_name("out/dump.json", "index"), "dump.index.json");
+        assert_eq!(part_file_name("dump", "002"), "dump.002");
+    }
+
+    #[test]
+    fn test_split_respects_limit_for_every_writer() -> Result<()> {
+        let files = create_test_files();
+        let tokenizer = Tokenizer::Heuristic;
+        let writers: Vec<Box<dyn OutputWriter>> = vec![
+            Box::new(MarkdownWriter::default()),
+            Box::new(JsonWriter::default()),
+            Box::new(XmlWriter::default()),
+        ];
+
+        for writer in writers {
+            let limit = SplitLimit::Tokens(1500);
+            let output = split(&files, writer.as_ref(), &tokenizer, limit, "dump.md")?;
+
+            assert!(output.parts.len() > 1);
+            for (i, rendered) in output.parts.iter().enumerate() {
+                assert_eq!(rendered.part.number, i + 1);
+                assert_eq!(rendered.part.total, output.parts.len());
+                assert!(tokenizer.count(&rendered.content) <= 1500);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_keeps_small_files_whole() -> Result<()> {
+        let files = create_test_files();
+        let output = split(
+            &files,
+            &MarkdownWriter::default(),
+            &Tokenizer::Heuristic,
+            SplitLimit::Bytes(6000),
+            "dump.md",
+        )?;
+
+        // Every small file appears exactly once, in order
+        let entries: Vec<&String> = output
+            .parts
+            .iter()
+            .flat_map(|p| p.part.entries.iter())
+            .collect();
+        let small: Vec<&&String> = entries
+            .iter()
+            .filter(|e| !e.starts_with("src/zz_huge.rs"))
+            .collect();
+        assert_eq!(small.len(), 12);
+        assert_eq!(*small[0], "src/mod00.rs");
+        assert_eq!(*small[11], "src/mod11.rs");
+
+        // The oversized file is cut into line ranges covering every line
+        let huge: Vec<&&String> = entries
+            .iter()
+            .filter(|e| e.starts_with("src/zz_huge.rs"))
+            .collect();
+        assert!(huge.len() > 1);
+        assert!(huge[0].starts_with("src/zz_huge.rs (lines 1-"));
+        assert!(huge.last().unwrap().ends_with("-400 of 400)"));
+
+        for rendered in &output.parts {
+            assert!(rendered.content.len() <= 6000);
+            assert!(rendered.content.starts_with(&format!(
+                "# Part {} of {}",
+                rendered.part.number,
+                output.parts.len()
+            )));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_index() -> Result<()> {
+        let files = create_test_files();
+        let output = split(
+            &files,
+            &JsonWriter::default(),
+            &Tokenizer::Heuristic,
+            SplitLimit::Tokens(2000),
+            "dump.json",
+        )?;
+
+        assert_eq!(output.index_file_name, "dump.index.json");
+        let index: serde_json::Value = serde_json::from_str(&output.index)?;
+        let parts = index["parts"].as_array().unwrap();
+        assert_eq!(parts.len(), output.parts.len());
+        assert_eq!(parts[0]["file"], "dump.001.json");
+        assert_eq!(parts[0]["files"][0], "src/mod00.rs");
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    error::{NomnomError, Result},
+    walker::{FileEntry, RepoPaths},
+};
+use git2::{Repository, Status, StatusOptions};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+/// Limits a local walk to the files git knows about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitSelection {
+    /// Files in the index
+    Tracked,
+    /// Modified, staged and untracked files, as listed by `git status`
+    Dirty,
+}
+
+impl GitSelection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitSelection::Tracked => "tracked",
+            GitSelection::Dirty => "dirty",
+        }
+    }
+
+    /// The command-line flag selecting it
+    pub fn flag(&self) -> &'static str {
+        match self {
+            GitSelection::Tracked => "--tracked-only",
+            GitSelection::Dirty => "--dirty",
+        }
+    }
+}
+
+/// Keeps the walked entries that `selection` lists in the repository
+/// containing `source`
+///
+/// Walk rules still apply: ignored, excluded and deleted files stay out even
+/// when git lists them.
+pub fn retain(source: &Path, selection: GitSelection, entries: &mut Vec<FileEntry>) -> Result<()> {
+    let repo = Repository::discover(source)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| {
+            NomnomError::Status(format!(
+                "{} needs a repository with a working tree",
+                selection.flag()
+            ))
+        })?
+        .canonicalize()?;
+
+    let paths = match selection {
+        GitSelection::Tracked => tracked_paths(&repo)?,
+        GitSelection::Dirty => dirty_paths(&repo)?,
+    };
+    info!("Git lists {} {} file(s)", paths.len(), selection.as_str());
+
+    let repo_paths = RepoPaths::new(source, &workdir)
+        .ok_or_else(|| NomnomError::Status(format!("{:?} is outside the repository", source)))?;
+    entries.retain(|entry| {
+        let keep = repo_paths
+            .of(entry)
+            .is_some_and(|path| paths.contains(&path));
+        if !keep {
+            debug!("Not {}: {:?}", selection.as_str(), entry.path);
+        }
+        keep
+    });
+    Ok(())
+}
+
+fn tracked_paths(repo: &Repository) -> Result<HashSet<PathBuf>> {
+    Ok(repo
+        .index()?
+        .iter()
+        .map(|entry| PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned()))
+        .collect())
+}
+
+fn dirty_paths(repo: &Repository) -> Result<HashSet<PathBuf>> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false)
+        .exclude_submodules(true);
+    let statuses = repo.statuses(Some(&mut opts))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| !entry.status().intersects(Status::CURRENT | Status::IGNORED))
+        .filter_map(|entry| entry.path().map(PathBuf::from))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, walker::Walker};
+    use git2::Signature;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn walked(root: &Path, source: &Path, selection: GitSelection) -> Vec<String> {
+        let mut entries = Walker::new(Config::default())
+            .walk_relative(source, root)
+            .unwrap();
+        retain(source, selection, &mut entries).unwrap();
+        entries
+            .iter()
+            .map(|e| e.path.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn test_tracked_and_dirty() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let repo = Repository::init(root).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        for path in ["src/lib.rs", "src/main.rs", "README.md"] {
+            fs::write(root.join(path), "committed\n").unwrap();
+        }
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+
+        fs::write(root.join("src/main.rs"), "modified\n").unwrap();
+        fs::write(root.join("src/staged.rs"), "staged\n").unwrap();
+        index.add_path(Path::new("src/staged.rs")).unwrap();
+        index.write().unwrap();
+        fs::write(root.join("src/scratch.rs"), "untracked\n").unwrap();
+        fs::write(root.join("notes.txt"), "untracked\n").unwrap();
+
+        assert_eq!(
+            walked(root, root, GitSelection::Tracked),
+            vec!["README.md", "src/lib.rs", "src/main.rs", "src/staged.rs"]
+        );
+        assert_eq!(
+            walked(root, root, GitSelection::Dirty),
+            vec![
+                "notes.txt",
+                "src/main.rs",
+                "src/scratch.rs",
+                "src/staged.rs"
+            ]
+        );
+        // A subdirectory source still matches by repository path
+        assert_eq!(
+            walked(root, &root.join("src"), GitSelection::Dirty),
+            vec!["src/main.rs", "src/scratch.rs", "src/staged.rs"]
+        );
+    }
+}
@@ -0,0 +1,545 @@
+use crate::{
+    config::StripConfig,
+    lines::{apply_edits, Edit, Edited},
+};
+use std::ops::Range;
+
+/// How a language writes comments and the string literals they can't start in
+struct Syntax {
+    /// Markers starting a comment that runs to the end of the line
+    line: &'static [&'static str],
+    /// Whether a line comment marker only counts at the start of a word, so
+    /// that `$#` in a shell script or `a#b` in YAML aren't comments
+    word_start: bool,
+    /// Opening and closing markers of block comments
+    block: &'static [(&'static str, &'static str)],
+    /// Line comment markers of doc comments, e.g. `///`
+    doc_line: &'static [&'static str],
+    /// Block comment markers of doc comments, e.g. `/**`
+    doc_block: &'static [&'static str],
+    quotes: &'static [Quote],
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Quote {
+    Delimited {
+        delimiter: &'static str,
+        /// Backslash escapes the next character
+        escapes: bool,
+        /// Otherwise an unterminated literal ends with its line
+        multiline: bool,
+        /// May open right after a letter or digit, as in Python's `f"…"`;
+        /// not so for C++ digit separators like `1'000`
+        after_word: bool,
+    },
+    /// `r"…"`, `r#"…"#` and their `br` forms
+    RustRaw,
+    /// `'a'` and `'\n'`, as opposed to lifetimes like `'a`
+    RustChar,
+}
+
+const fn quote(delimiter: &'static str, escapes: bool, multiline: bool) -> Quote {
+    Quote::Delimited {
+        delimiter,
+        escapes,
+        multiline,
+        after_word: true,
+    }
+}
+
+const DOUBLE: Quote = quote("\"", true, false);
+const SINGLE: Quote = quote("'", true, false);
+const TRIPLE_DOUBLE: Quote = quote("\"\"\"", true, true);
+const TRIPLE_SINGLE: Quote = quote("'''", true, true);
+const BACKTICK: Quote = quote("`", true, true);
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+const MARKUP_BLOCK: &[(&str, &str)] = &[("<!--", "-->")];
+
+const NONE: Syntax = Syntax {
+    line: &[],
+    word_start: false,
+    block: &[],
+    doc_line: &[],
+    doc_block: &[],
+    quotes: &[],
+};
+const RUST: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    doc_line: &["///", "//!"],
+    doc_block: &["/**", "/*!"],
+    quotes: &[Quote::RustRaw, quote("\"", true, true), Quote::RustChar],
+    ..NONE
+};
+const C: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    doc_block: &["/**"],
+    quotes: &[
+        DOUBLE,
+        Quote::Delimited {
+            delimiter: "'",
+            escapes: true,
+            multiline: false,
+            after_word: false,
+        },
+    ],
+    ..NONE
+};
+const JAVASCRIPT: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    doc_block: &["/**"],
+    quotes: &[DOUBLE, SINGLE, BACKTICK],
+    ..NONE
+};
+/// Java, Kotlin, Scala, Groovy, Swift, Dart and C#
+const JVM_LIKE: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    doc_line: &["///"],
+    doc_block: &["/**"],
+    quotes: &[TRIPLE_DOUBLE, DOUBLE, SINGLE],
+    ..NONE
+};
+const GO: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    quotes: &[DOUBLE, SINGLE, quote("`", false, true)],
+    ..NONE
+};
+const PHP: Syntax = Syntax {
+    line: &["//", "#"],
+    block: C_BLOCK,
+    doc_block: &["/**"],
+    quotes: &[DOUBLE, SINGLE],
+    ..NONE
+};
+const PROTOBUF: Syntax = Syntax {
+    line: &["//"],
+    block: C_BLOCK,
+    quotes: &[DOUBLE, SINGLE],
+    ..NONE
+};
+const CSS: Syntax = Syntax {
+    block: C_BLOCK,
+    quotes: &[DOUBLE, SINGLE],
+    ..NONE
+};
+/// `//` only at the start of a word, so `url(http://…)` stays whole
+const SCSS: Syntax = Syntax {
+    line: &["//"],
+    word_start: true,
+    ..CSS
+};
+const PYTHON: Syntax = Syntax {
+    line: &["#"],
+    word_start: true,
+    quotes: &[TRIPLE_DOUBLE, TRIPLE_SINGLE, DOUBLE, SINGLE],
+    ..NONE
+};
+/// Shell, Ruby, Perl, R, YAML, Dockerfile and GraphQL
+const HASH: Syntax = Syntax {
+    line: &["#"],
+    word_start: true,
+    quotes: &[DOUBLE, quote("'", false, false)],
+    ..NONE
+};
+const TOML: Syntax = Syntax {
+    line: &["#"],
+    word_start: true,
+    quotes: &[
+        TRIPLE_DOUBLE,
+        quote("'''", false, true),
+        DOUBLE,
+        quote("'", false, false),
+    ],
+    ..NONE
+};
+const HCL: Syntax = Syntax {
+    line: &["#", "//"],
+    word_start: true,
+    block: C_BLOCK,
+    quotes: &[DOUBLE],
+    ..NONE
+};
+const SQL: Syntax = Syntax {
+    line: &["--"],
+    block: C_BLOCK,
+    quotes: &[quote("'", false, true), quote("\"", false, false)],
+    ..NONE
+};
+const LUA: Syntax = Syntax {
+    line: &["--"],
+    block: &[("--[[", "]]")],
+    quotes: &[DOUBLE, SINGLE],
+    ..NONE
+};
+const POWERSHELL: Syntax = Syntax {
+    line: &["#"],
+    word_start: true,
+    block: &[("<#", "#>")],
+    quotes: &[DOUBLE, quote("'", false, false)],
+    ..NONE
+};
+const INI: Syntax = Syntax {
+    line: &[";", "#"],
+    word_start: true,
+    ..NONE
+};
+const MARKUP: Syntax = Syntax {
+    block: MARKUP_BLOCK,
+    ..NONE
+};
+
+/// Comment syntax by the language names `code_language` gives; languages
+/// without one still get blank-line and trailing-whitespace stripping
+fn syntax(language: &str) -> &'static Syntax {
+    match language {
+        "rust" => &RUST,
+        "c" | "cpp" => &C,
+        "javascript" | "typescript" => &JAVASCRIPT,
+        "java" | "kotlin" | "scala" | "groovy" | "swift" | "dart" | "csharp" => &JVM_LIKE,
+        "go" => &GO,
+        "php" => &PHP,
+        "protobuf" => &PROTOBUF,
+        "css" => &CSS,
+        "scss" | "less" => &SCSS,
+        "python" => &PYTHON,
+        "bash" | "ruby" | "perl" | "r" | "yaml" | "dockerfile" | "graphql" => &HASH,
+        "toml" => &TOML,
+        "hcl" => &HCL,
+        "sql" => &SQL,
+        "lua" => &LUA,
+        "powershell" => &POWERSHELL,
+        "ini" => &INI,
+        "html" | "xml" | "markdown" | "vue" | "svelte" => &MARKUP,
+        _ => &NONE,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommentKind {
+    Line,
+    Block,
+    Doc,
+}
+
+/// Removes what `options` selects from `text`, written in `language`
+///
+/// Lines left empty by a removed comment are dropped, and code before a
+/// removed trailing comment loses the whitespace that separated them. String
+/// literals are never changed.
+pub fn strip(text: &str, language: &str, options: &StripConfig) -> Edited {
+    let removed: Vec<Edit> = comments(text, syntax(language))
+        .into_iter()
+        .filter(|(_, kind)| match kind {
+            CommentKind::Line => options.line_comments,
+            CommentKind::Block => options.block_comments,
+            CommentKind::Doc => options.doc_comments,
+        })
+        .map(|(range, _)| (range, String::new()))
+        .collect();
+
+    // Where the comments were cut out of what's left
+    let mut removed_len = 0;
+    let cuts: Vec<usize> = removed
+        .iter()
+        .map(|(range, _)| {
+            let at = range.start - removed_len;
+            removed_len += range.len();
+            at
+        })
+        .collect();
+    let kept = apply_edits(text, &removed);
+
+    let mut result = String::with_capacity(kept.text.len());
+    let mut origins = Vec::new();
+    let mut cuts = cuts.into_iter().peekable();
+    let mut start = 0;
+    let mut blank_run = 0;
+    for (index, line) in kept.text.split_inclusive('\n').enumerate() {
+        let end = start + line.len();
+        let mut cut = false;
+        while cuts
+            .next_if(|&at| at < end || end == kept.text.len())
+            .is_some()
+        {
+            cut = true;
+        }
+        start = end;
+
+        let (content, ending) = split_ending(line);
+        let blank = content.trim().is_empty();
+        if cut && blank {
+            continue;
+        }
+        if blank {
+            blank_run += 1;
+            // Leading blank lines go too, e.g. after a license header
+            if options.blank_lines && (blank_run > 1 || result.is_empty()) {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        if cut || options.trailing_whitespace {
+            result.push_str(content.trim_end_matches([' ', '\t']));
+        } else {
+            result.push_str(content);
+        }
+        result.push_str(ending);
+        origins.push(index);
+    }
+    kept.then(Edited {
+        text: result,
+        origins,
+    })
+}
+
+/// Splits a line into its content and its `\n` or `\r\n` ending
+fn split_ending(line: &str) -> (&str, &str) {
+    let content = line
+        .strip_suffix('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .unwrap_or(line);
+    line.split_at(content.len())
+}
+
+/// Finds the comments in `text`, skipping over string literals
+fn comments(text: &str, syntax: &Syntax) -> Vec<(Range<usize>, CommentKind)> {
+    let bytes = text.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    'scan: while i < bytes.len() {
+        let rest = &bytes[i..];
+        for (open, close) in syntax.block {
+            if rest.starts_with(open.as_bytes()) {
+                let end =
+                    find(bytes, i + open.len(), close).map_or(bytes.len(), |at| at + close.len());
+                let kind = if is_doc(rest, syntax.doc_block) {
+                    CommentKind::Doc
+                } else {
+                    CommentKind::Block
+                };
+                found.push((i..end, kind));
+                i = end;
+                continue 'scan;
+            }
+        }
+        let word_start = i == 0 || bytes[i - 1].is_ascii_whitespace();
+        for marker in syntax.line {
+            // A shebang isn't a comment to strip
+            if rest.starts_with(marker.as_bytes())
+                && (word_start || !syntax.word_start)
+                && !(i == 0 && rest.starts_with(b"#!"))
+            {
+                let end = find(bytes, i, "\n").unwrap_or(bytes.len());
+                let kind = if is_doc(rest, syntax.doc_line) {
+                    CommentKind::Doc
+                } else {
+                    CommentKind::Line
+                };
+                found.push((i..end, kind));
+                i = end;
+                continue 'scan;
+            }
+        }
+        for quote in syntax.quotes {
+            if let Some(end) = quote.literal_end(text, i) {
+                i = end;
+                continue 'scan;
+            }
+        }
+        i += 1;
+    }
+    found
+}
+
+/// Whether a comment starting `rest` opens with one of the doc `markers`,
+/// and not with a longer run like `////` or `/**/`
+fn is_doc(rest: &[u8], markers: &[&str]) -> bool {
+    markers.iter().any(|marker| {
+        let marker = marker.as_bytes();
+        rest.starts_with(marker)
+            && rest
+                .get(marker.len())
+                .is_none_or(|&next| next != marker[marker.len() - 1] && next != b'/')
+    })
+}
+
+fn find(bytes: &[u8], from: usize, needle: &str) -> Option<usize> {
+    bytes[from..]
+        .windows(needle.len())
+        .position(|window| window == needle.as_bytes())
+        .map(|at| from + at)
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+impl Quote {
+    /// The end of the literal this quote opens at `i`, if it opens one there
+    fn literal_end(&self, text: &str, i: usize) -> Option<usize> {
+        let bytes = text.as_bytes();
+        let after_word = i > 0 && is_word_byte(bytes[i - 1]);
+        match *self {
+            Quote::Delimited {
+                delimiter,
+                escapes,
+                multiline,
+                after_word: may_follow_word,
+            } => {
+                if !bytes[i..].starts_with(delimiter.as_bytes()) || (after_word && !may_follow_word)
+                {
+                    return None;
+                }
+                let mut j = i + delimiter.len();
+                while j < bytes.len() {
+                    if escapes && bytes[j] == b'\\' {
+                        j += 2;
+                    } else if bytes[j..].starts_with(delimiter.as_bytes()) {
+                        return Some(j + delimiter.len());
+                    } else if !multiline && bytes[j] == b'\n' {
+                        return Some(j);
+                    } else {
+                        j += 1;
+                    }
+                }
+                Some(bytes.len())
+            }
+            Quote::RustRaw => {
+                let rest = &bytes[i..];
+                let prefix = if rest.starts_with(b"br") {
+                    2
+                } else if rest.starts_with(b"r") {
+                    1
+                } else {
+                    return None;
+                };
+                if after_word {
+                    return None;
+                }
+                let hashes = rest[prefix..].iter().take_while(|&&b| b == b'#').count();
+                if rest.get(prefix + hashes) != Some(&b'"') {
+                    return None;
+                }
+                let close = format!("\"{}", "#".repeat(hashes));
+                let body = i + prefix + hashes + 1;
+                Some(find(bytes, body, &close).map_or(bytes.len(), |at| at + close.len()))
+            }
+            Quote::RustChar => {
+                if bytes[i] != b'\'' {
+                    return None;
+                }
+                let mut chars = text[i + 1..].char_indices();
+                let (_, first) = chars.next()?;
+                if first == '\\' {
+                    // An escape, up to `'\u{10FFFF}'`
+                    return find(&bytes[..bytes.len().min(i + 12)], i + 2, "'").map(|at| at + 1);
+                }
+                let (at, second) = chars.next()?;
+                (second == '\'').then_some(i + 1 + at + 1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> StripConfig {
+        StripConfig {
+            line_comments: true,
+            block_comments: true,
+            doc_comments: true,
+            blank_lines: true,
+            trailing_whitespace: true,
+        }
+    }
+
+    #[test]
+    fn test_strip_rust() {
+        let text = r##"// Copyright (c) Example Corp.
+// Licensed under MIT.
+
+//! Crate docs
+
+/// Parses a URL
+fn parse(url: &str) -> &str { // trailing
+    let a = "http://example.com // not a comment";
+    let b = r#"/* nor this */"#;
+    let c = '/';
+    /* old code
+    let d = 1;
+    */
+
+
+    url.trim() /* inline */ .as_ref()
+}
+
+fn lifetime<'a>(s: &'a str) -> &'a str { s } // done
+"##;
+        let options = StripConfig {
+            doc_comments: false,
+            ..all()
+        };
+        assert_eq!(
+            strip(text, "rust", &options).text,
+            r##"//! Crate docs
+
+/// Parses a URL
+fn parse(url: &str) -> &str {
+    let a = "http://example.com // not a comment";
+    let b = r#"/* nor this */"#;
+    let c = '/';
+
+    url.trim()  .as_ref()
+}
+
+fn lifetime<'a>(s: &'a str) -> &'a str { s }
+"##
+        );
+
+        // Doc comments too
+        let stripped = strip(text, "rust", &all()).text;
+        assert!(!stripped.contains("Crate docs"));
+        assert!(!stripped.contains("Parses a URL"));
+    }
+
+    #[test]
+    fn test_strip_hash_languages() {
+        let text =
+            "#!/usr/bin/env python3\n# helper\nURL = \"a#b\"  # the url\ns = '''\n# kept\n'''\n";
+        assert_eq!(
+            strip(text, "python", &all()).text,
+            "#!/usr/bin/env python3\nURL = \"a#b\"\ns = '''\n# kept\n'''\n"
+        );
+
+        let text = "echo $# ${#list[@]}\n  # indented\necho 'it''s' # done\n";
+        assert_eq!(
+            strip(text, "bash", &all()).text,
+            "echo $# ${#list[@]}\necho 'it''s'\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_whitespace_only() {
+        let options = StripConfig {
+            line_comments: false,
+            block_comments: false,
+            doc_comments: false,
+            blank_lines: true,
+            trailing_whitespace: true,
+        };
+        let text = "\n\n# Title   \r\n\r\n\r\n\r\nText\t\n\n\n";
+        assert_eq!(
+            strip(text, "markdown", &options).text,
+            "# Title\r\n\r\nText\n\n"
+        );
+        // Unknown languages only get whitespace stripping
+        assert_eq!(strip("a // b  \n", "", &all()).text, "a // b\n");
+    }
+}
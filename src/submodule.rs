@@ -0,0 +1,166 @@
+use crate::auth::{redact_url, url_host, Authenticator};
+use crate::error::{NomnomError, Result};
+use crate::git::{self, CloneOptions};
+use crate::walker::FileEntry;
+use git2::{build::CheckoutBuilder, ErrorCode, Repository};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, info, warn};
+
+const GITMODULES_FILE: &str = ".gitmodules";
+
+/// Initialises and checks out the submodules of a fresh clone, recursively,
+/// at the commits recorded in the parent's tree
+///
+/// Only submodules under `subpath` are fetched. Each one is fetched shallow by
+/// commit id first, falling back to its full history when the server won't
+/// hand out a commit no branch or tag points at.
+///
+/// `.gitmodules` is written by whoever controls the repository, so for a
+/// remote parent, submodules on the local filesystem are skipped, and
+/// credentials are only offered to submodules on the parent's host.
+pub fn update(repo: &Repository, subpath: Option<&str>, options: &CloneOptions) -> Result<()> {
+    let head = repo.head()?.peel_to_commit()?;
+    if head.tree()?.get_path(Path::new(GITMODULES_FILE)).is_err() {
+        return Ok(());
+    }
+    // libgit2 only reads .gitmodules from the working tree, which a sparse
+    // checkout leaves out
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force().path(GITMODULES_FILE);
+    repo.checkout_head(Some(&mut checkout))?;
+
+    let parent_url = repo
+        .find_remote("origin")?
+        .url()
+        .unwrap_or_default()
+        .to_string();
+    let parent_is_remote = !git::is_local_url(&parent_url);
+
+    let subpath = subpath.map(|s| Path::new(s.trim_matches('/')));
+    for mut submodule in repo.submodules()? {
+        let path = submodule.path().to_path_buf();
+        if subpath.is_some_and(|subpath| !path.starts_with(subpath)) {
+            debug!("Skipping submodule outside the subpath: {:?}", path);
+            continue;
+        }
+        let Some(oid) = submodule.head_id() else {
+            debug!("Submodule {:?} is not in the tree, skipping", path);
+            continue;
+        };
+
+        // Resolves relative URLs against the parent's origin
+        submodule.init(false)?;
+        let name = submodule.name().unwrap_or_default().to_string();
+        let url = repo
+            .config()?
+            .get_string(&format!("submodule.{}.url", name))?;
+        if parent_is_remote && git::is_local_url(&url) {
+            warn!(
+                "Skipping submodule {:?}: local URL {} in a remote repository",
+                path,
+                redact_url(&url)
+            );
+            continue;
+        }
+        info!("Fetching submodule {:?} from {}", path, redact_url(&url));
+
+        let auth = if url_host(&url) == url_host(&parent_url) {
+            Authenticator::from_env(&url, &options.ssh_keys)
+        } else {
+            debug!(
+                "Submodule {:?} is on another host, fetching without credentials",
+                path
+            );
+            Authenticator::anonymous()
+        };
+        let sub_repo = submodule.repo_init(true)?;
+        let sha = oid.to_string();
+        if let Err(e) = git::fetch_with_auth(&sub_repo, &url, Some(&sha), options, &auth) {
+            if options.depth.is_none() || is_auth_error(&e) {
+                return Err(e);
+            }
+            debug!("Shallow fetch of submodule {:?} failed: {}", path, e);
+            let full_history = CloneOptions {
+                depth: None,
+                ..options.clone()
+            };
+            git::fetch_with_auth(&sub_repo, &url, Some(&sha), &full_history, &auth)?;
+        }
+        git::checkout_commit(&sub_repo, oid, None)?;
+        update(&sub_repo, None, options)?;
+    }
+    Ok(())
+}
+
+fn is_auth_error(error: &NomnomError) -> bool {
+    match error {
+        NomnomError::Auth(_) => true,
+        NomnomError::Git(e) => e.code() == ErrorCode::Auth,
+        _ => false,
+    }
+}
+
+/// Records on each entry the output path of the submodule checkout it lives
+/// in, so the directory tree can mark submodule boundaries
+///
+/// Sources outside a git repository, or without submodules, are left alone.
+pub fn mark(source: &Path, files: &mut [FileEntry]) {
+    let Ok(repo) = Repository::discover(source) else {
+        return;
+    };
+    let mut roots = Vec::new();
+    collect_roots(&repo, &mut roots);
+    if roots.is_empty() {
+        return;
+    }
+    // Innermost submodule first
+    roots.sort_by_key(|root| std::cmp::Reverse(root.components().count()));
+    debug!("Submodule checkouts: {:?}", roots);
+
+    for entry in files.iter_mut() {
+        let within = |path: &Path| -> Option<usize> {
+            roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .map(|inner| inner.components().count())
+        };
+        let depth = match within(&entry.absolute_path) {
+            Some(depth) => depth,
+            None => match entry.absolute_path.canonicalize() {
+                Ok(canonical) => match within(&canonical) {
+                    Some(depth) => depth,
+                    None => continue,
+                },
+                Err(_) => continue,
+            },
+        };
+        // Drop the path within the submodule from the output path
+        let components: Vec<Component> = entry.path.components().collect();
+        if depth < components.len() {
+            let root: PathBuf = components[..components.len() - depth].iter().collect();
+            entry.submodule = Some(root.to_string_lossy().replace('\\', "/"));
+        }
+    }
+}
+
+/// Adds the working directories of initialised submodules, recursively
+fn collect_roots(repo: &Repository, roots: &mut Vec<PathBuf>) {
+    let Some(workdir) = repo.workdir().and_then(|w| w.canonicalize().ok()) else {
+        return;
+    };
+    let submodules = match repo.submodules() {
+        Ok(submodules) => submodules,
+        Err(e) => {
+            warn!("Cannot read submodules of {:?}: {}", workdir, e);
+            return;
+        }
+    };
+    for submodule in submodules {
+        // Uninitialised submodules have no checkout to walk
+        let Ok(sub_repo) = submodule.open() else {
+            continue;
+        };
+        roots.push(workdir.join(submodule.path()));
+        collect_roots(&sub_repo, roots);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    error::{NomnomError, Result},
+    processor::{FileContent, ProcessedFile},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{collections::HashMap, path::Path};
+use tiktoken_rs::CoreBPE;
+use tracing::{debug, warn};
+
+/// Pre-tokenization pattern from cl100k_base, also used for custom vocabularies
+const CL100K_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+pub fn tokens_len(chars: usize) -> usize {
+    // ceil(chars / 4 * 1.3)
+    (chars * 13).div_ceil(40)
+}
+
+/// Counts tokens in rendered output
+///
+/// Supported names:
+/// - `heuristic`: character-based estimate, no vocabulary needed
+/// - `cl100k` / `cl100k_base`: bundled GPT-4 era vocabulary
+/// - `o200k` / `o200k_base`: bundled GPT-4o era vocabulary
+/// - any other value is treated as a path to a tiktoken vocabulary file
+///   (one `<base64 token> <rank>` pair per line)
+pub enum Tokenizer {
+    Heuristic,
+    Bpe { name: String, bpe: Box<CoreBPE> },
+}
+
+impl Tokenizer {
+    pub fn from_name(name: &str) -> Result<Self> {
+        let bpe = match name {
+            "heuristic" => return Ok(Tokenizer::Heuristic),
+            "cl100k" | "cl100k_base" => tiktoken_rs::cl100k_base(),
+            "o200k" | "o200k_base" => tiktoken_rs::o200k_base(),
+            path => return load_vocabulary(Path::new(path)),
+        }
+        .map_err(|e| NomnomError::Tokenizer(format!("{}: {}", name, e)))?;
+
+        debug!("Loaded bundled tokenizer: {}", name);
+        Ok(Tokenizer::Bpe {
+            name: name.to_string(),
+            bpe: Box::new(bpe),
+        })
+    }
+
+    /// Same as [`Tokenizer::from_name`], but falls back to the heuristic
+    /// with a warning when the vocabulary cannot be loaded
+    pub fn from_name_or_heuristic(name: &str) -> Self {
+        Self::from_name(name).unwrap_or_else(|e| {
+            warn!("{}; falling back to heuristic token counts", e);
+            Tokenizer::Heuristic
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Tokenizer::Heuristic => "heuristic",
+            Tokenizer::Bpe { name, .. } => name,
+        }
+    }
+
+    /// Whether counts are exact for the vocabulary rather than an estimate
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Tokenizer::Heuristic)
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Heuristic => tokens_len(text.len()),
+            Tokenizer::Bpe { bpe, .. } => bpe.encode_ordinary(text).len(),
+        }
+    }
+
+    /// Counts the tokens of a file's rendered content or stub description
+    pub fn count_file(&self, file: &ProcessedFile) -> usize {
+        match &file.content {
+            FileContent::Text(text)
+            | FileContent::Binary(text)
+            | FileContent::Oversized(text)
+            | FileContent::Error(text)
+            | FileContent::Omitted(text) => self.count(text),
+        }
+    }
+}
+
+fn load_vocabulary(path: &Path) -> Result<Tokenizer> {
+    let data = std::fs::read_to_string(path).map_err(|e| {
+        NomnomError::Tokenizer(format!(
+            "cannot read vocabulary file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let mut encoder: HashMap<Vec<u8>, u32> = HashMap::new();
+    for (line_idx, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let invalid = || {
+            NomnomError::Tokenizer(format!(
+                "invalid vocabulary entry at {}:{}",
+                path.display(),
+                line_idx + 1
+            ))
+        };
+        let (token, rank) = line.split_once(' ').ok_or_else(invalid)?;
+        let token = STANDARD.decode(token).map_err(|_| invalid())?;
+        let rank: u32 = rank.trim().parse().map_err(|_| invalid())?;
+        encoder.insert(token, rank);
+    }
+
+    let bpe = CoreBPE::new(
+        encoder.into_iter().collect(),
+        Default::default(),
+        CL100K_PATTERN,
+    )
+    .map_err(|e| NomnomError::Tokenizer(format!("{}: {}", path.display(), e)))?;
+
+    debug!("Loaded vocabulary file: {}", path.display());
+    Ok(Tokenizer::Bpe {
+        name: path.display().to_string(),
+        bpe: Box::new(bpe),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_tokens_len() {
+        assert_eq!(tokens_len(0), 0);
+        assert_eq!(tokens_len(40), 13);
+        assert_eq!(tokens_len(41), 14);
+    }
+
+    #[test]
+    fn test_heuristic() -> Result<()> {
+        let tokenizer = Tokenizer::from_name("heuristic")?;
+        assert!(!tokenizer.is_exact());
+        assert_eq!(tokenizer.count(&"a".repeat(40)), 13);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundled_vocabularies() -> Result<()> {
+        let cl100k = Tokenizer::from_name("cl100k")?;
+        assert!(cl100k.is_exact());
+        assert_eq!(cl100k.count("hello world"), 2);
+        assert_eq!(cl100k.count(""), 0);
+
+        let o200k = Tokenizer::from_name("o200k_base")?;
+        assert_eq!(o200k.name(), "o200k_base");
+        assert_eq!(o200k.count("hello world"), 2);
+
+        // Special tokens in file content are counted as ordinary text
+        assert!(cl100k.count("<|endoftext|>") > 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vocabulary_file() -> Result<()> {
+        let vocab = NamedTempFile::new()?;
+        let mut lines: Vec<String> = (0u32..256)
+            .map(|b| format!("{} {}", STANDARD.encode([b as u8]), b))
+            .collect();
+        lines.push(format!("{} 256", STANDARD.encode("ab")));
+        std::fs::write(vocab.path(), lines.join("\n"))?;
+
+        let tokenizer = Tokenizer::from_name(&vocab.path().to_string_lossy())?;
+        assert_eq!(tokenizer.count("abab"), 2);
+        assert_eq!(tokenizer.count("abc"), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_vocabulary_falls_back() -> Result<()> {
+        let vocab = NamedTempFile::new()?;
+        std::fs::write(vocab.path(), "not-a-vocabulary-line")?;
+        let name = vocab.path().to_string_lossy().to_string();
+
+        assert!(matches!(
+            Tokenizer::from_name(&name),
+            Err(NomnomError::Tokenizer(_))
+        ));
+        assert!(!Tokenizer::from_name_or_heuristic(&name).is_exact());
+        assert!(!Tokenizer::from_name_or_heuristic("/no/such/vocab").is_exact());
+        Ok(())
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    attributes::AttributeRules,
+    error::{NomnomError, Result},
+    walker::{
+        is_excluded, EntrySource, FileEntry, IgnoreMatcher, Walker, GIT_IGNORE_FILES,
+        NOMNOM_IGNORE_FILE,
+    },
+};
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// A local repository read at a ref, e.g. `.@v1.4#src`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSource {
+    /// Path inside the repository's working copy
+    pub path: PathBuf,
+    /// Branch, tag, or commit to read; `HEAD` when only a subpath was given
+    pub reference: String,
+    /// Optional directory within the tree at `reference`
+    pub subpath: Option<String>,
+}
+
+/// Parses a local `path@ref#subpath` source
+///
+/// Returns `None` when the source names an existing path (so paths that
+/// happen to contain `@` or `#` keep working) or has neither suffix.
+pub fn parse_tree_source(source: &str) -> Option<TreeSource> {
+    if Path::new(source).exists() {
+        return None;
+    }
+
+    let (rest, subpath) = match source.rsplit_once('#') {
+        Some((rest, subpath)) => (rest, Some(subpath.trim_matches('/').to_string())),
+        None => (source, None),
+    };
+    let (path, reference) = match rest.rsplit_once('@') {
+        Some((path, reference)) => (path, Some(reference.to_string())),
+        None => (rest, None),
+    };
+    if reference.is_none() && subpath.is_none() {
+        return None;
+    }
+
+    let path = if path.is_empty() { "." } else { path };
+    if !Path::new(path).exists() {
+        return None;
+    }
+
+    Some(TreeSource {
+        path: PathBuf::from(path),
+        reference: reference
+            .filter(|r| !r.is_empty())
+            .unwrap_or_else(|| "HEAD".to_string()),
+        subpath: subpath.filter(|s| !s.is_empty()),
+    })
+}
+
+/// A blob found while walking the tree
+struct TreeBlob {
+    path: PathBuf,
+    oid: Oid,
+}
+
+/// Lists the files of a tree source as walker entries, read from the object
+/// database without touching the working copy
+///
+/// Paths are relative to the repository root. `.nomnomignore` files in the
+/// tree always apply, `.gitignore` and `.ignore` files when `ignore_git` is
+/// enabled, and include/exclude globs are relative to the requested directory.
+pub fn tree_files(source: &TreeSource, walker: &Walker) -> Result<Vec<FileEntry>> {
+    let repo = Repository::discover(&source.path)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| {
+            NomnomError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "reading a ref needs a repository with a working tree",
+            ))
+        })?
+        .canonicalize()?;
+
+    // The source path and the subpath combine into one directory in the tree
+    let mut prefix = source
+        .path
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    if let Some(ref subpath) = source.subpath {
+        prefix.push(subpath);
+    }
+
+    let commit = repo
+        .revparse_single(&source.reference)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| {
+            NomnomError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Reference '{}' not found in repository", source.reference),
+            ))
+        })?;
+    info!(
+        "Reading {} at {} ({})",
+        if prefix.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            prefix.display().to_string()
+        },
+        source.reference,
+        &commit.id().to_string()[..7]
+    );
+
+    let tree = commit.tree()?;
+    if !prefix.as_os_str().is_empty() {
+        let is_dir = tree
+            .get_path(&prefix)
+            .is_ok_and(|entry| entry.kind() == Some(ObjectType::Tree));
+        if !is_dir {
+            return Err(NomnomError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "Subpath '{}' not found at {}",
+                    prefix.display(),
+                    source.reference
+                ),
+            )));
+        }
+    }
+
+    let ignore_git = walker.ignores_git();
+    let mut blobs = Vec::new();
+    let mut ignore_files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        let Some(name) = entry.name() else {
+            return TreeWalkResult::Ok;
+        };
+        let path = Path::new(dir).join(name);
+        match entry.kind() {
+            // Only descend into directories that can contain wanted files or
+            // ignore files applying to them
+            Some(ObjectType::Tree) if !(prefix.starts_with(&path) || path.starts_with(&prefix)) => {
+                TreeWalkResult::Skip
+            }
+            Some(ObjectType::Blob) if entry.filemode() != i32::from(git2::FileMode::Link) => {
+                if name == NOMNOM_IGNORE_FILE || (ignore_git && GIT_IGNORE_FILES.contains(&name)) {
+                    ignore_files.push(TreeBlob {
+                        path: path.clone(),
+                        oid: entry.id(),
+                    });
+                }
+                if path.starts_with(&prefix) {
+                    blobs.push(TreeBlob {
+                        path,
+                        oid: entry.id(),
+                    });
+                }
+                TreeWalkResult::Ok
+            }
+            // Symlinks and submodules have no content to dump
+            _ => TreeWalkResult::Ok,
+        }
+    })?;
+
+    let ignores = build_ignores(&repo, &workdir, &ignore_files)?;
+    let overrides = walker.overrides(&workdir.join(&prefix))?;
+    // Attributes come from the working copy; libgit2 can't read them at a ref
+    let attributes = AttributeRules::discover(&workdir, walker.gitattributes());
+    let max_size = walker.max_size()?;
+    let odb = repo.odb()?;
+
+    let mut entries = Vec::new();
+    for blob in blobs {
+        let absolute_path = workdir.join(&blob.path);
+        if ignores.is_ignored(&absolute_path) {
+            debug!("Ignored in tree: {:?}", blob.path);
+            continue;
+        }
+        if let Some(ref overrides) = overrides {
+            if is_excluded(overrides, &absolute_path, &workdir) {
+                debug!("Excluded in tree: {:?}", blob.path);
+                continue;
+            }
+        }
+
+        // Only the header is read here; content is loaded when processed
+        let (size, _) = odb.read_header(blob.oid)?;
+        let size = size as u64;
+        let mut entry = FileEntry {
+            is_binary: walker.is_binary_by_extension(&blob.path),
+            is_oversized: size > max_size,
+            path: blob.path,
+            absolute_path,
+            size,
+            source: EntrySource::Blob {
+                repo: repo.path().to_path_buf(),
+                oid: blob.oid,
+            },
+            change: None,
+            submodule: None,
+            omitted: None,
+        };
+        if let Some(ref rules) = attributes {
+            let path = entry.path.clone();
+            if !rules.apply(&mut entry, &path) {
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    info!("Found {} files in tree", entries.len());
+    Ok(entries)
+}
+
+/// Reads a blob of an `EntrySource::Blob`
+pub(crate) fn read_blob(repo: &Path, oid: Oid) -> Result<Vec<u8>> {
+    let repo = Repository::open(repo)?;
+    let blob = repo.find_blob(oid)?;
+    Ok(blob.content().to_vec())
+}
+
+/// Compiles the ignore files found in the tree
+fn build_ignores(
+    repo: &Repository,
+    workdir: &Path,
+    ignore_files: &[TreeBlob],
+) -> Result<IgnoreMatcher> {
+    let mut ignores = IgnoreMatcher::default();
+    for file in ignore_files {
+        let blob = repo.find_blob(file.oid)?;
+        ignores.add(&workdir.join(&file.path), blob.content())?;
+    }
+    Ok(ignores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use git2::{Commit, Signature};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_all(repo: &Repository, message: &str) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::FORCE, None)
+            .unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parents: Vec<Commit> = repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<&Commit> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    fn create_repo() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub fn one() {}\n").unwrap();
+        fs::write(root.join("src/generated/api.rs"), "// generated\n").unwrap();
+        fs::write(root.join("src/secret.key"), "hunter2\n").unwrap();
+        fs::write(root.join("src/.nomnomignore"), "*.key\n").unwrap();
+        fs::write(root.join("README.md"), "# Readme\n").unwrap();
+        commit_all(&repo, "initial");
+        repo.tag_lightweight(
+            "v1",
+            &repo.head().unwrap().peel(ObjectType::Commit).unwrap(),
+            false,
+        )
+        .unwrap();
+
+        fs::write(root.join("src/lib.rs"), "pub fn two() {}\n").unwrap();
+        fs::write(root.join("src/new.rs"), "pub fn new() {}\n").unwrap();
+        commit_all(&repo, "second");
+        (dir, repo)
+    }
+
+    fn paths(entries: &[FileEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| e.path.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    fn content(entry: &FileEntry) -> String {
+        match entry.source {
+            EntrySource::Blob { ref repo, oid } => {
+                String::from_utf8_lossy(&read_blob(repo, oid).unwrap()).into_owned()
+            }
+            _ => panic!("expected content from the tree"),
+        }
+    }
+
+    #[test]
+    fn test_parse_tree_source() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+
+        let source = parse_tree_source(&format!("{}@v1.4#src/lib", root)).unwrap();
+        assert_eq!(source.path, dir.path());
+        assert_eq!(source.reference, "v1.4");
+        assert_eq!(source.subpath.as_deref(), Some("src/lib"));
+
+        let source = parse_tree_source(&format!("{}#src", root)).unwrap();
+        assert_eq!(source.reference, "HEAD");
+
+        let source = parse_tree_source("@main").unwrap();
+        assert_eq!(source.path, Path::new("."));
+        assert_eq!(source.reference, "main");
+
+        // Plain and existing paths are not tree sources
+        assert_eq!(parse_tree_source(&root), None);
+        fs::create_dir(dir.path().join("@types")).unwrap();
+        assert_eq!(parse_tree_source(&format!("{}/@types", root)), None);
+        assert_eq!(parse_tree_source("/nonexistent/dir@v1"), None);
+    }
+
+    #[test]
+    fn test_tree_files_at_ref() {
+        let (dir, _repo) = create_repo();
+        let walker = Walker::new(Config::default());
+        let source = TreeSource {
+            path: dir.path().to_path_buf(),
+            reference: "v1".to_string(),
+            subpath: Some("src".to_string()),
+        };
+
+        let entries = tree_files(&source, &walker).unwrap();
+        assert_eq!(
+            paths(&entries),
+            vec!["src/.nomnomignore", "src/generated/api.rs", "src/lib.rs"]
+        );
+        assert_eq!(content(&entries[2]), "pub fn one() {}\n");
+        assert_eq!(entries[2].size, 16);
+
+        // The working copy is untouched
+        assert_eq!(
+            fs::read_to_string(dir.path().join("src/lib.rs")).unwrap(),
+            "pub fn two() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_files_globs_and_errors() {
+        let (dir, _repo) = create_repo();
+        let walker = Walker::new(Config {
+            exclude: vec!["src/generated/**".to_string()],
+            ..Config::default()
+        });
+        let source = TreeSource {
+            path: dir.path().to_path_buf(),
+            reference: "HEAD".to_string(),
+            subpath: None,
+        };
+        let entries = tree_files(&source, &walker).unwrap();
+        assert_eq!(
+            paths(&entries),
+            vec!["README.md", "src/.nomnomignore", "src/lib.rs", "src/new.rs"]
+        );
+
+        let missing = TreeSource {
+            subpath: Some("nope".to_string()),
+            ..source.clone()
+        };
+        assert!(tree_files(&missing, &walker)
+            .unwrap_err()
+            .to_string()
+            .contains("Subpath 'nope' not found at HEAD"));
+
+        let bad_ref = TreeSource {
+            reference: "v9".to_string(),
+            ..source
+        };
+        assert!(tree_files(&bad_ref, &walker)
+            .unwrap_err()
+            .to_string()
+            .contains("Reference 'v9' not found"));
+    }
+
+    #[test]
+    fn test_nomnomignore_wins_over_gitignore() {
+        let (dir, repo) = create_repo();
+        let root = dir.path();
+        fs::write(root.join("src/.gitignore"), "*.txt\n").unwrap();
+        fs::write(root.join("src/.nomnomignore"), "*.key\n!notes.txt\n").unwrap();
+        fs::write(root.join("src/notes.txt"), "kept\n").unwrap();
+        fs::write(root.join("src/other.txt"), "ignored\n").unwrap();
+        commit_all(&repo, "ignore files");
+
+        let source = TreeSource {
+            path: root.to_path_buf(),
+            reference: "HEAD".to_string(),
+            subpath: Some("src".to_string()),
+        };
+        let entries = tree_files(&source, &Walker::new(Config::default())).unwrap();
+        assert_eq!(
+            paths(&entries),
+            vec![
+                "src/.gitignore",
+                "src/.nomnomignore",
+                "src/generated/api.rs",
+                "src/lib.rs",
+                "src/new.rs",
+                "src/notes.txt"
+            ]
+        );
+    }
+}
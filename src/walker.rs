@@ -1,13 +1,41 @@
 use crate::{
-    config::Config,
+    archive::{self, ArchiveKind},
+    attributes::AttributeRules,
+    config::{ArchiveConfig, Config, GitAttributesConfig},
+    diff::FileChange,
     error::{NomnomError, Result},
 };
-use ignore::{WalkBuilder, WalkState};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder, WalkState,
+};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::{debug, warn};
 
-#[derive(Debug, Clone)]
+/// Ignore file for excluding paths from output without touching `.gitignore`
+pub const NOMNOM_IGNORE_FILE: &str = ".nomnomignore";
+
+/// Ignore files that are only honoured when `ignore_git` is enabled
+pub(crate) const GIT_IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Where a file's bytes are read from
+#[derive(Debug, Clone, Default)]
+pub enum EntrySource {
+    /// Read from `absolute_path` on disk
+    #[default]
+    Disk,
+    /// Already loaded, e.g. a member of an archive
+    Memory(Arc<[u8]>),
+    /// A blob in the object database of the repository at `repo`, e.g. from a
+    /// git tree, read when the file is processed
+    Blob { repo: PathBuf, oid: git2::Oid },
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct FileEntry {
     /// The path to display in output (may be relative)
     pub path: PathBuf,
@@ -16,12 +44,110 @@ pub struct FileEntry {
     pub size: u64,
     pub is_binary: bool,
     pub is_oversized: bool,
+    pub source: EntrySource,
+    /// How the file changed, when walking a diff
+    pub change: Option<FileChange>,
+    /// Output path of the submodule the file belongs to, if any
+    pub submodule: Option<String>,
+    /// Placeholder shown instead of the content, e.g. for generated files
+    pub omitted: Option<String>,
 }
 
 pub struct Walker {
     config: Config,
 }
 
+/// Maps entries walked from `source` to their paths relative to the root of
+/// the repository containing it
+pub(crate) struct RepoPaths<'a> {
+    source: &'a Path,
+    /// `source` relative to the repository root
+    prefix: PathBuf,
+}
+
+impl<'a> RepoPaths<'a> {
+    /// `workdir` is the canonical repository working directory
+    pub(crate) fn new(source: &'a Path, workdir: &Path) -> Option<Self> {
+        let prefix = source
+            .canonicalize()
+            .ok()?
+            .strip_prefix(workdir)
+            .ok()?
+            .to_path_buf();
+        Some(Self { source, prefix })
+    }
+
+    pub(crate) fn of(&self, entry: &FileEntry) -> Option<PathBuf> {
+        let relative = entry.absolute_path.strip_prefix(self.source).ok()?;
+        if relative.as_os_str().is_empty() {
+            Some(self.prefix.clone())
+        } else {
+            Some(self.prefix.join(relative))
+        }
+    }
+}
+
+/// Ignore files compiled to match one path at a time, for files that don't
+/// come from a walk, like the changes of a diff or the blobs of a tree
+#[derive(Debug, Default)]
+pub(crate) struct IgnoreMatcher {
+    /// Each file's matcher with its absolute directory, in precedence order
+    matchers: Vec<(PathBuf, Gitignore)>,
+    /// Sort key of each matcher: deeper directories first, then within a
+    /// directory `.nomnomignore`, `.ignore` and `.gitignore`, as in a walk
+    precedence: Vec<(std::cmp::Reverse<usize>, usize)>,
+}
+
+impl IgnoreMatcher {
+    /// Adds the ignore file at absolute `path`, with `contents`
+    pub(crate) fn add(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut builder = GitignoreBuilder::new(&dir);
+        for line in String::from_utf8_lossy(contents).lines() {
+            builder.add_line(Some(path.to_path_buf()), line)?;
+        }
+        let rank = match path.file_name().and_then(|name| name.to_str()) {
+            Some(NOMNOM_IGNORE_FILE) => 0,
+            Some(".ignore") => 1,
+            _ => 2,
+        };
+        let key = (std::cmp::Reverse(dir.components().count()), rank);
+        let index = self.precedence.partition_point(|other| *other <= key);
+        self.precedence.insert(index, key);
+        self.matchers.insert(index, (dir, builder.build()?));
+        Ok(())
+    }
+
+    /// Whether the file at absolute `path` is ignored, by a pattern matching
+    /// it or one of its directories
+    pub(crate) fn is_ignored(&self, path: &Path) -> bool {
+        for (dir, ignore) in &self.matchers {
+            if !path.starts_with(dir) {
+                continue;
+            }
+            let matched = ignore.matched_path_or_any_parents(path, false);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+/// Whether include/exclude globs exclude `path`, or one of its directories
+/// below `root` that a walk would not have descended into
+pub(crate) fn is_excluded(overrides: &Override, path: &Path, root: &Path) -> bool {
+    overrides.matched(path, false).is_ignore()
+        || path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_os_str().is_empty() && *dir != root)
+            .any(|dir| overrides.matched(dir, true).is_ignore())
+}
+
 impl Walker {
     pub fn new(config: Config) -> Self {
         Self { config }
@@ -52,6 +178,45 @@ impl Walker {
         self.walk_internal(source, thread_count, Some(base_path.as_ref()))
     }
 
+    /// Builds entries for an explicit list of files, in the order given,
+    /// without walking any directory
+    ///
+    /// Include/exclude globs apply relative to the working directory. Ignore
+    /// files and `.gitattributes` don't, since the files were named outright.
+    /// Paths that aren't files, and repeats, are skipped.
+    pub fn list<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Vec<FileEntry>> {
+        let max_size = self.config.resolve_max_size()?;
+        let overrides = self.overrides(Path::new("."))?;
+
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            if !path.is_file() {
+                warn!("Listed path is not a file, skipping: {:?}", path);
+                continue;
+            }
+            if let Some(ref overrides) = overrides {
+                if is_excluded(overrides, path, Path::new(".")) {
+                    debug!("Excluded from list: {:?}", path);
+                    continue;
+                }
+            }
+            if !seen.insert(path.to_path_buf()) {
+                continue;
+            }
+
+            match self.process_file_with_base(path, max_size, None) {
+                Ok(Some(file_entry)) => entries.push(file_entry),
+                Ok(None) => debug!("Skipped file: {:?}", path),
+                Err(e) => warn!("Error processing file {:?}: {}", path, e),
+            }
+        }
+
+        debug!("Listed {} files", entries.len());
+        Ok(entries)
+    }
+
     fn walk_internal<P: AsRef<Path>>(
         &self,
         source: P,
@@ -66,19 +231,7 @@ impl Walker {
         debug!("Max file size: {}", max_size);
         debug!("Ignore git: {}", self.config.ignore_git);
 
-        let mut builder = WalkBuilder::new(source);
-        let ignore_git = self.config.ignore_git;
-        builder
-            .hidden(false)
-            .git_ignore(ignore_git)
-            .git_global(ignore_git)
-            .git_exclude(ignore_git)
-            .ignore(ignore_git)
-            .filter_entry(move |entry| {
-                let path = entry.path();
-                !path.is_dir() || !ignore_git || path.file_name().is_none_or(|n| n != ".git")
-            })
-            .sort_by_file_name(|a, b| a.cmp(b));
+        let mut builder = self.builder(source)?;
 
         if thread_count == 1 {
             // Single-threaded processing
@@ -102,6 +255,8 @@ impl Walker {
                 }
             }
 
+            self.apply_gitattributes(source, &mut entries);
+            self.expand_archives(&mut entries);
             entries.sort_by(|a, b| a.path.cmp(&b.path));
             debug!("Found {} files", entries.len());
             Ok(entries)
@@ -146,12 +301,185 @@ impl Walker {
                 .map_err(|_| NomnomError::Output("Failed to lock entries mutex".to_string()))?
                 .clone();
 
+            self.apply_gitattributes(source, &mut entries);
+            self.expand_archives(&mut entries);
             entries.sort_by(|a, b| a.path.cmp(&b.path));
             debug!("Found {} files", entries.len());
             Ok(entries)
         }
     }
 
+    fn builder(&self, source: &Path) -> Result<WalkBuilder> {
+        let mut builder = WalkBuilder::new(source);
+        let ignore_git = self.config.ignore_git;
+        builder
+            .hidden(false)
+            .git_ignore(ignore_git)
+            .git_global(ignore_git)
+            .git_exclude(ignore_git)
+            .ignore(ignore_git)
+            // Honoured regardless of `ignore_git`
+            .add_custom_ignore_filename(NOMNOM_IGNORE_FILE)
+            // Skips `.git` directories, and the `.git` files of submodule checkouts
+            .filter_entry(move |entry| !ignore_git || entry.file_name() != ".git")
+            .sort_by_file_name(|a, b| a.cmp(b));
+
+        if let Some(overrides) = self.overrides(source)? {
+            builder.overrides(overrides);
+        }
+
+        Ok(builder)
+    }
+
+    pub(crate) fn max_size(&self) -> Result<u64> {
+        self.config.resolve_max_size()
+    }
+
+    pub(crate) fn ignores_git(&self) -> bool {
+        self.config.ignore_git
+    }
+
+    pub(crate) fn gitattributes(&self) -> &GitAttributesConfig {
+        &self.config.gitattributes
+    }
+
+    pub(crate) fn archives(&self) -> &ArchiveConfig {
+        &self.config.archives
+    }
+
+    /// Replaces walked archives with the files inside them, listed under the
+    /// archive's path, when `archives.expand_nested` is set
+    ///
+    /// An archive that can't be read stays in the output as a binary file.
+    fn expand_archives(&self, entries: &mut Vec<FileEntry>) {
+        if !self.config.archives.expand_nested {
+            return;
+        }
+        let mut expanded = Vec::with_capacity(entries.len());
+        for entry in entries.drain(..) {
+            if entry.omitted.is_some() || ArchiveKind::of(&entry.path).is_none() {
+                expanded.push(entry);
+                continue;
+            }
+            match archive::entries(&entry.absolute_path, self) {
+                Ok(members) => expanded.extend(members.into_iter().map(|mut member| {
+                    member.path = entry.path.join(&member.path);
+                    member
+                })),
+                Err(e) => {
+                    warn!(
+                        "Cannot read archive {:?}, keeping it as is: {}",
+                        entry.path, e
+                    );
+                    expanded.push(entry);
+                }
+            }
+        }
+        *entries = expanded;
+    }
+
+    /// Applies the configured `.gitattributes` actions to walked entries,
+    /// dropping skipped files and stubbing the rest
+    fn apply_gitattributes(&self, source: &Path, entries: &mut Vec<FileEntry>) {
+        let Some(rules) = AttributeRules::discover(source, &self.config.gitattributes) else {
+            return;
+        };
+        // Attributes are looked up by path relative to the repository root
+        let Some(repo_paths) = rules
+            .workdir()
+            .and_then(|workdir| RepoPaths::new(source, &workdir))
+        else {
+            return;
+        };
+        entries.retain_mut(|entry| match repo_paths.of(entry) {
+            Some(path) => rules.apply(entry, &path),
+            None => true,
+        });
+    }
+
+    /// Lists the ignore files that apply when walking `source`
+    ///
+    /// `.nomnomignore` files are always listed, from `source` and its parent
+    /// directories as well as within it; `.gitignore` and `.ignore` files only
+    /// when `ignore_git` is enabled.
+    pub fn ignore_files<P: AsRef<Path>>(&self, source: P) -> Result<Vec<PathBuf>> {
+        let source = source.as_ref();
+        let is_ignore_file = |name: &str| {
+            name == NOMNOM_IGNORE_FILE
+                || (self.config.ignore_git && GIT_IGNORE_FILES.contains(&name))
+        };
+
+        let mut found = Vec::new();
+        if let Ok(absolute) = source.canonicalize() {
+            for dir in absolute.ancestors().skip(1) {
+                let candidate = dir.join(NOMNOM_IGNORE_FILE);
+                if candidate.is_file() {
+                    found.push(candidate);
+                }
+            }
+            found.reverse();
+        }
+
+        for result in self.builder(source)?.build() {
+            let entry = match result {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Walk error: {}", e);
+                    continue;
+                }
+            };
+            let is_file = entry.file_type().is_some_and(|t| t.is_file());
+            if is_file && entry.file_name().to_str().is_some_and(is_ignore_file) {
+                found.push(entry.into_path());
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Compiles the ignore files that apply when walking `source`, to match
+    /// files found some other way against them
+    pub(crate) fn ignore_matcher(&self, source: &Path) -> Result<IgnoreMatcher> {
+        let mut matcher = IgnoreMatcher::default();
+        for path in self.ignore_files(source)? {
+            match fs::read(&path) {
+                Ok(contents) => matcher.add(&path, &contents)?,
+                Err(e) => warn!("Cannot read ignore file {:?}: {}", path, e),
+            }
+        }
+        Ok(matcher)
+    }
+
+    /// Builds the configured include/exclude globs, relative to `root`
+    ///
+    /// Includes act as a whitelist for files; directories are still descended
+    /// into so that nested matches are found. Excludes take precedence, and a
+    /// directory matched by an exclude is never descended into.
+    pub fn overrides(&self, root: &Path) -> Result<Option<Override>> {
+        if self.config.include.is_empty() && self.config.exclude.is_empty() {
+            return Ok(None);
+        }
+
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &self.config.include {
+            builder.add(glob)?;
+        }
+        for glob in &self.config.exclude {
+            builder.add(&format!("!{}", glob))?;
+            // `dir/**` only matches what is inside `dir`; also match the
+            // directory itself so the walk skips it entirely
+            if let Some(dir) = glob.strip_suffix("/**").filter(|d| !d.is_empty()) {
+                builder.add(&format!("!{}/", dir))?;
+            }
+        }
+
+        debug!(
+            "Include globs: {:?}, exclude globs: {:?}",
+            self.config.include, self.config.exclude
+        );
+        Ok(Some(builder.build()?))
+    }
+
     fn process_file_with_base(
         &self,
         path: &Path,
@@ -185,10 +513,14 @@ impl Walker {
             size,
             is_binary,
             is_oversized,
+            source: EntrySource::Disk,
+            change: None,
+            submodule: None,
+            omitted: None,
         }))
     }
 
-    fn is_binary_by_extension(&self, path: &Path) -> bool {
+    pub(crate) fn is_binary_by_extension(&self, path: &Path) -> bool {
         if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
             match extension.to_lowercase().as_str() {
                 // Images
@@ -261,4 +593,221 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_list() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        for path in ["main.rs", "logo.png", "vendor/dep.rs", "debug.log"] {
+            fs::write(root.join(path), "content").unwrap();
+        }
+        fs::write(root.join("big.txt"), "x".repeat(2048)).unwrap();
+
+        let walker = Walker::new(Config {
+            max_size: "1K".to_string(),
+            exclude: vec!["*.log".to_string(), "vendor".to_string()],
+            ..Config::default()
+        });
+        let paths: Vec<PathBuf> = [
+            "main.rs",
+            "logo.png",
+            "big.txt",
+            "missing.rs",
+            "vendor",
+            "main.rs",
+            "vendor/dep.rs",
+            "debug.log",
+        ]
+        .iter()
+        .map(|path| root.join(path))
+        .collect();
+        let entries = walker.list(&paths)?;
+
+        // Listed order is kept, not sorted
+        let listed: Vec<&PathBuf> = entries.iter().map(|e| &e.path).collect();
+        assert_eq!(listed, vec![&paths[0], &paths[1], &paths[2]]);
+        assert!(entries[1].is_binary);
+        assert!(entries[2].is_oversized);
+        Ok(())
+    }
+
+    fn walked_paths(walker: &Walker, root: &Path) -> Result<Vec<String>> {
+        Ok(walker
+            .walk_relative(root, root)?
+            .into_iter()
+            .map(|e| e.path.to_string_lossy().replace('\\', "/"))
+            .collect())
+    }
+
+    #[test]
+    fn test_include_exclude_globs() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for path in [
+            "README.md",
+            "src/lib.rs",
+            "src/api/mod.rs",
+            "src/api/notes.txt",
+            "src/generated/schema.rs",
+            "src/api/generated/client.rs",
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "content").unwrap();
+        }
+
+        let config = Config {
+            include: vec!["src/**/*.rs".to_string()],
+            exclude: vec!["**/generated/**".to_string()],
+            ..create_test_config()
+        };
+        let walker = Walker::new(config);
+        assert_eq!(
+            walked_paths(&walker, root)?,
+            vec!["src/api/mod.rs", "src/lib.rs"]
+        );
+
+        // Excluded directories are not descended into at all
+        let overrides = walker.overrides(root)?.unwrap();
+        assert!(overrides
+            .matched(root.join("src/generated"), true)
+            .is_ignore());
+
+        let config = Config {
+            exclude: vec!["*.txt".to_string()],
+            ..create_test_config()
+        };
+        let paths = walked_paths(&Walker::new(config), root)?;
+        assert_eq!(paths.len(), 5);
+        assert!(!paths.contains(&"src/api/notes.txt".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let config = Config {
+            include: vec!["src/[".to_string()],
+            ..create_test_config()
+        };
+        assert!(Walker::new(config).overrides(Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn test_nomnomignore() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for path in [
+            "src/lib.rs",
+            "tests/fixtures/big.json",
+            "tests/snapshots/a.snap",
+            "tests/it.rs",
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "content").unwrap();
+        }
+        fs::write(root.join(NOMNOM_IGNORE_FILE), "tests/fixtures/\n").unwrap();
+        fs::write(root.join("tests").join(NOMNOM_IGNORE_FILE), "*.snap\n").unwrap();
+
+        for ignore_git in [true, false] {
+            let config = Config {
+                ignore_git,
+                ..create_test_config()
+            };
+            let walker = Walker::new(config);
+            assert_eq!(
+                walked_paths(&walker, root)?,
+                vec![
+                    ".nomnomignore",
+                    "src/lib.rs",
+                    "tests/.nomnomignore",
+                    "tests/it.rs"
+                ]
+            );
+
+            let ignore_files = walker.ignore_files(root)?;
+            assert!(ignore_files.ends_with(&[
+                root.join(NOMNOM_IGNORE_FILE),
+                root.join("tests").join(NOMNOM_IGNORE_FILE)
+            ]));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gitattributes() -> Result<()> {
+        use crate::config::AttributeAction;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        git2::Repository::init(root).unwrap();
+        for path in [
+            "src/lib.rs",
+            "src/api.pb.rs",
+            "src/vendor/jquery.js",
+            "docs/internal.md",
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "content").unwrap();
+        }
+        fs::write(
+            root.join(".gitattributes"),
+            "*.pb.rs linguist-generated\nsrc/vendor/** linguist-vendored\ndocs/** export-ignore\n",
+        )
+        .unwrap();
+
+        // Attributes apply by repository path when walking a subdirectory
+        let entries = Walker::new(create_test_config())
+            .walk_relative(&root.join("src"), &root.to_path_buf())?;
+        let stubs: Vec<(String, Option<String>)> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.path.to_string_lossy().replace('\\', "/"),
+                    e.omitted.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            stubs,
+            vec![
+                (
+                    "src/api.pb.rs".to_string(),
+                    Some("[omitted: linguist-generated]".to_string())
+                ),
+                ("src/lib.rs".to_string(), None),
+                (
+                    "src/vendor/jquery.js".to_string(),
+                    Some("[omitted: linguist-vendored]".to_string())
+                ),
+            ]
+        );
+
+        assert!(!walked_paths(&Walker::new(create_test_config()), root)?
+            .contains(&"docs/internal.md".to_string()));
+
+        let config = Config {
+            gitattributes: crate::config::GitAttributesConfig {
+                export_ignore: AttributeAction::Include,
+                linguist_generated: AttributeAction::Skip,
+                linguist_vendored: AttributeAction::Include,
+            },
+            ..create_test_config()
+        };
+        assert_eq!(
+            walked_paths(&Walker::new(config), root)?,
+            vec![
+                ".gitattributes",
+                "docs/internal.md",
+                "src/lib.rs",
+                "src/vendor/jquery.js"
+            ]
+        );
+
+        Ok(())
+    }
 }
@@ -8,7 +8,7 @@ use std::path::PathBuf;
 /// Test binary detection using actual test files in the repository
 #[test]
 fn test_binary_detection_with_test_files() {
-    let processor = Processor::new(Config::default());
+    let processor = Processor::new(Config::default()).unwrap();
 
     // Test 1: PNG image file (should be detected as binary by content)
     let png_path = PathBuf::from("test/test-image.png");
@@ -19,6 +19,7 @@ fn test_binary_detection_with_test_files() {
             size: std::fs::metadata(&png_path).unwrap().len(),
             is_binary: false, // Will be detected by content analysis
             is_oversized: false,
+            ..Default::default()
         };
 
         let result = processor.process_file(&png_entry);
@@ -40,6 +41,7 @@ fn test_binary_detection_with_test_files() {
             size: std::fs::metadata(&bin_path).unwrap().len(),
             is_binary: false, // Will be detected by content analysis
             is_oversized: false,
+            ..Default::default()
         };
 
         let result = processor.process_file(&bin_entry);
@@ -55,6 +57,7 @@ fn test_binary_detection_with_test_files() {
             size: std::fs::metadata(&exe_path).unwrap().len(),
             is_binary: false, // Will be detected by content analysis
             is_oversized: false,
+            ..Default::default()
         };
 
         let result = processor.process_file(&exe_entry);
@@ -73,6 +76,7 @@ fn test_binary_detection_with_test_files() {
             size: std::fs::metadata(&config_path).unwrap().len(),
             is_binary: false,
             is_oversized: false,
+            ..Default::default()
         };
 
         let result = processor.process_file(&config_entry);
@@ -104,7 +108,7 @@ fn test_binary_detection_with_test_files() {
 /// Test that verifies binary detection logging messages
 #[test]
 fn test_binary_detection_logging() {
-    let processor = Processor::new(Config::default());
+    let processor = Processor::new(Config::default()).unwrap();
 
     // Test with a known binary file if it exists
     let png_path = PathBuf::from("test/test-image.png");
@@ -115,6 +119,7 @@ fn test_binary_detection_logging() {
             size: std::fs::metadata(&png_path).unwrap().len(),
             is_binary: false, // This will trigger content-based binary detection
             is_oversized: false,
+            ..Default::default()
         };
 
         // This should generate a log message about binary detection by content
@@ -130,6 +135,7 @@ fn test_binary_detection_logging() {
         size: 100,
         is_binary: true, // This will trigger extension-based binary detection
         is_oversized: false,
+        ..Default::default()
     };
 
     // This should generate a log message about binary detection by extension
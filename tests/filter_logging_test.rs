@@ -21,17 +21,24 @@ fn test_current_filter_behavior() {
                 pattern: r"(?i)(password|api[_-]?key)\s*[:=]\s*\S+".to_string(),
                 file_pattern: None,
                 threshold: None,
+                charset: None,
+                min_length: None,
+                min_entropy: None,
             },
             FilterConfig {
                 r#type: "truncate".to_string(),
                 pattern: r"<style[^>]*>.*?</style>".to_string(),
                 file_pattern: Some(r"\.html?$".to_string()),
                 threshold: None,
+                charset: None,
+                min_length: None,
+                min_entropy: None,
             },
         ],
+        ..Config::default()
     };
 
-    let processor = Processor::new(config);
+    let processor = Processor::new(config).unwrap();
 
     // Test 1: Redaction filter on a regular file with multi-line content
     let temp_file = NamedTempFile::new().unwrap();
@@ -45,6 +52,7 @@ fn test_current_filter_behavior() {
         size: std::fs::metadata(&file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     let result = processor.process_file(&entry).unwrap();
@@ -71,6 +79,7 @@ fn test_current_filter_behavior() {
         size: std::fs::metadata(&html_file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     let result = processor.process_file(&html_entry).unwrap();
@@ -92,6 +101,7 @@ fn test_current_filter_behavior() {
         size: std::fs::metadata(&binary_file_path).unwrap().len(),
         is_binary: false, // Will be detected as binary by content
         is_oversized: false,
+        ..Default::default()
     };
 
     let result = processor.process_file(&binary_entry);
@@ -107,6 +117,7 @@ fn test_current_filter_behavior() {
         size: std::fs::metadata(&css_file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     let result = processor.process_file(&css_entry).unwrap();
@@ -140,17 +151,24 @@ fn test_enhanced_filter_logging() {
                 pattern: r"(?i)(password|secret|key)\s*[:=]\s*\S+".to_string(),
                 file_pattern: None,
                 threshold: None,
+                charset: None,
+                min_length: None,
+                min_entropy: None,
             },
             FilterConfig {
                 r#type: "truncate".to_string(),
                 pattern: r"<div[^>]*>.*?</div>".to_string(),
                 file_pattern: Some(r"\.html?$".to_string()),
                 threshold: None,
+                charset: None,
+                min_length: None,
+                min_entropy: None,
             },
         ],
+        ..Config::default()
     };
 
-    let processor = Processor::new(config);
+    let processor = Processor::new(config).unwrap();
 
     // Test multi-line file with multiple matches on different lines
     let temp_file = NamedTempFile::new().unwrap();
@@ -171,6 +189,7 @@ password=yetanothersecret"#;
         size: std::fs::metadata(&file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     // Process the file - this should trigger detailed logging
@@ -208,6 +227,7 @@ password=yetanothersecret"#;
         size: std::fs::metadata(&html_file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     // Process the HTML file - this should trigger truncation logging
@@ -20,10 +20,14 @@ fn test_safe_logging_mode() {
             pattern: r"(?i)password\s*[:=]\s*\S+".to_string(),
             file_pattern: None,
             threshold: None,
+            charset: None,
+            min_length: None,
+            min_entropy: None,
         }],
+        ..Config::default()
     };
 
-    let safe_processor = Processor::new(safe_config);
+    let safe_processor = Processor::new(safe_config).unwrap();
 
     // Create test file with secrets
     let temp_file = NamedTempFile::new().unwrap();
@@ -37,6 +41,7 @@ fn test_safe_logging_mode() {
         size: std::fs::metadata(&file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     // Process with safe logging - this should generate logs with character positions
@@ -71,10 +76,14 @@ fn test_unsafe_logging_mode() {
             pattern: r"(?i)password\s*[:=]\s*\S+".to_string(),
             file_pattern: None,
             threshold: None,
+            charset: None,
+            min_length: None,
+            min_entropy: None,
         }],
+        ..Config::default()
     };
 
-    let unsafe_processor = Processor::new(unsafe_config);
+    let unsafe_processor = Processor::new(unsafe_config).unwrap();
 
     // Create test file with secrets
     let temp_file = NamedTempFile::new().unwrap();
@@ -88,6 +97,7 @@ fn test_unsafe_logging_mode() {
         size: std::fs::metadata(&file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     // Process with unsafe logging - this should generate logs with actual text
@@ -131,10 +141,14 @@ fn test_safe_logging_with_truncation() {
             pattern: r"<script[^>]*>.*?</script>".to_string(),
             file_pattern: Some(r"\.html?$".to_string()),
             threshold: None,
+            charset: None,
+            min_length: None,
+            min_entropy: None,
         }],
+        ..Config::default()
     };
 
-    let processor = Processor::new(config);
+    let processor = Processor::new(config).unwrap();
 
     // Create HTML file with script tag
     let temp_file = NamedTempFile::new().unwrap();
@@ -149,6 +163,7 @@ fn test_safe_logging_with_truncation() {
         size: std::fs::metadata(&file_path).unwrap().len(),
         is_binary: false,
         is_oversized: false,
+        ..Default::default()
     };
 
     // Process the file - should truncate script content and log safely